@@ -0,0 +1,327 @@
+use crate::TransactionRangeError;
+use crate::{get_transaction_data, naive_datetime_from_millis, TimeRange, TransactionData};
+use polars::error::PolarsError;
+use polars::frame::DataFrame;
+use polars::prelude::{NamedFrom, Series};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FeeStatsError {
+    #[error("invalid bucket size: {0}ms")]
+    InvalidBucket(i64),
+    #[error(transparent)]
+    TransactionRangeError(#[from] TransactionRangeError),
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Percentiles {
+    pub p10: u128,
+    pub p50: u128,
+    pub p90: u128,
+    pub p99: u128,
+}
+
+impl Percentiles {
+    // nearest-rank percentiles, values must be sorted
+    fn from_sorted(values: &[u128]) -> Self {
+        let rank = |p: usize| -> u128 {
+            if values.is_empty() {
+                return 0;
+            }
+            let index = (p * values.len()).div_ceil(100).max(1) - 1;
+            values[index.min(values.len() - 1)]
+        };
+        Self {
+            p10: rank(10),
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+        }
+    }
+
+    pub fn to_array(&self) -> [u128; 4] {
+        [self.p10, self.p50, self.p90, self.p99]
+    }
+}
+
+/// Fee distribution of the transactions first seen in `[bucket_start_ms, bucket_start_ms + bucket_ms)`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct FeeBucket {
+    pub bucket_start_ms: i64,
+    pub count: u64,
+    /// transaction type -> count, for files with `txType`
+    pub type_counts: BTreeMap<i64, u64>,
+    /// transactions without type with the same gas price, tip cap and fee cap
+    pub legacy_count: u64,
+    /// transactions without type with different gas price, tip cap or fee cap
+    pub dynamic_fee_count: u64,
+    pub gas_price: Percentiles,
+    pub gas_tip_cap: Percentiles,
+    pub gas_fee_cap: Percentiles,
+    /// sum of transferred value in wei
+    pub total_value: u128,
+}
+
+/// Computes fee statistics for transaction-data files in the given time range.
+pub fn fee_stats(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+    bucket_ms: i64,
+) -> Result<Vec<FeeBucket>, FeeStatsError> {
+    if bucket_ms <= 0 {
+        return Err(FeeStatsError::InvalidBucket(bucket_ms));
+    }
+    let transactions = get_transaction_data(data_dir, range)?;
+    fee_buckets(&transactions, bucket_ms)
+}
+
+/// Groups the transactions into buckets of `bucket_ms` by the time they were first seen, buckets
+/// are aligned to the unix epoch and only returned if they contain a transaction.
+pub fn fee_buckets(
+    transactions: &[TransactionData],
+    bucket_ms: i64,
+) -> Result<Vec<FeeBucket>, FeeStatsError> {
+    #[derive(Default)]
+    struct Values {
+        bucket: FeeBucket,
+        gas_price: Vec<u128>,
+        gas_tip_cap: Vec<u128>,
+        gas_fee_cap: Vec<u128>,
+    }
+
+    if bucket_ms <= 0 {
+        return Err(FeeStatsError::InvalidBucket(bucket_ms));
+    }
+    let mut buckets: BTreeMap<i64, Values> = BTreeMap::new();
+    for tx in transactions {
+        let bucket_start_ms = tx.timestamp_ms - tx.timestamp_ms.rem_euclid(bucket_ms);
+        let values = buckets.entry(bucket_start_ms).or_default();
+
+        let gas_price = tx.gas_price.parse::<u128>().ok();
+        let gas_tip_cap = tx.gas_tip_cap.parse::<u128>().ok();
+        let gas_fee_cap = tx.gas_fee_cap.parse::<u128>().ok();
+
+        values.bucket.count += 1;
+        match tx.tx_type {
+            Some(tx_type) => *values.bucket.type_counts.entry(tx_type).or_default() += 1,
+            // files of the older layouts don't have the type
+            None if gas_tip_cap == gas_fee_cap && gas_fee_cap == gas_price => {
+                values.bucket.legacy_count += 1
+            }
+            None => values.bucket.dynamic_fee_count += 1,
+        }
+        values.bucket.total_value = values
+            .bucket
+            .total_value
+            .saturating_add(tx.value.parse::<u128>().unwrap_or_default());
+
+        values.gas_price.extend(gas_price);
+        values.gas_tip_cap.extend(gas_tip_cap);
+        values.gas_fee_cap.extend(gas_fee_cap);
+    }
+
+    Ok(buckets
+        .into_iter()
+        .map(|(bucket_start_ms, mut values)| {
+            values.gas_price.sort_unstable();
+            values.gas_tip_cap.sort_unstable();
+            values.gas_fee_cap.sort_unstable();
+            FeeBucket {
+                bucket_start_ms,
+                gas_price: Percentiles::from_sorted(&values.gas_price),
+                gas_tip_cap: Percentiles::from_sorted(&values.gas_tip_cap),
+                gas_fee_cap: Percentiles::from_sorted(&values.gas_fee_cap),
+                ..values.bucket
+            }
+        })
+        .collect())
+}
+
+/// Converts fee buckets to a dataframe, fees are stored as u64 (saturated) and total value
+/// as a base 10 string because it can overflow u64. Each transaction type seen in any bucket gets
+/// a `type<N>Count` column.
+pub fn fee_buckets_dataframe(buckets: &[FeeBucket]) -> Result<DataFrame, PolarsError> {
+    let fee = |v: u128| u64::try_from(v).unwrap_or(u64::MAX);
+    let mut columns = vec![
+        Series::new(
+            "bucketStart",
+            buckets
+                .iter()
+                .map(|b| naive_datetime_from_millis(b.bucket_start_ms))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new("count", buckets.iter().map(|b| b.count).collect::<Vec<_>>()),
+        Series::new(
+            "legacyCount",
+            buckets.iter().map(|b| b.legacy_count).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "dynamicFeeCount",
+            buckets
+                .iter()
+                .map(|b| b.dynamic_fee_count)
+                .collect::<Vec<_>>(),
+        ),
+    ];
+    let tx_types = buckets
+        .iter()
+        .flat_map(|b| b.type_counts.keys().copied())
+        .collect::<BTreeSet<_>>();
+    for tx_type in tx_types {
+        columns.push(Series::new(
+            &format!("type{}Count", tx_type),
+            buckets
+                .iter()
+                .map(|b| b.type_counts.get(&tx_type).copied().unwrap_or_default())
+                .collect::<Vec<_>>(),
+        ));
+    }
+    let metrics = [
+        (
+            "gasPrice",
+            buckets.iter().map(|b| b.gas_price).collect::<Vec<_>>(),
+        ),
+        ("gasTipCap", buckets.iter().map(|b| b.gas_tip_cap).collect()),
+        ("gasFeeCap", buckets.iter().map(|b| b.gas_fee_cap).collect()),
+    ];
+    for (name, percentiles) in metrics {
+        for (i, suffix) in ["P10", "P50", "P90", "P99"].into_iter().enumerate() {
+            columns.push(Series::new(
+                &format!("{}{}", name, suffix),
+                percentiles
+                    .iter()
+                    .map(|p| fee(p.to_array()[i]))
+                    .collect::<Vec<_>>(),
+            ));
+        }
+    }
+    columns.push(Series::new(
+        "totalValue",
+        buckets
+            .iter()
+            .map(|b| b.total_value.to_string())
+            .collect::<Vec<_>>(),
+    ));
+    DataFrame::new(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tx;
+
+    fn fees(timestamp_ms: i64, gas_price: &str, tip: &str, fee: &str) -> TransactionData {
+        tx(timestamp_ms, &format!("0x{}", timestamp_ms))
+            .with_gas_price(gas_price)
+            .with_gas_tip_cap(tip)
+            .with_gas_fee_cap(fee)
+    }
+
+    #[test]
+    fn test_fee_percentiles() {
+        let txs = (1..=100)
+            .map(|i| {
+                let fee = (i * 10).to_string();
+                fees(i, &fee, "1", &fee)
+            })
+            .collect::<Vec<_>>();
+
+        let buckets = fee_buckets(&txs, 60_000).unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].bucket_start_ms, 0);
+        assert_eq!(buckets[0].count, 100);
+        assert_eq!(
+            buckets[0].gas_fee_cap,
+            Percentiles {
+                p10: 100,
+                p50: 500,
+                p90: 900,
+                p99: 990,
+            }
+        );
+        assert_eq!(buckets[0].gas_tip_cap.p99, 1);
+    }
+
+    #[test]
+    fn test_fee_buckets_by_timestamp() {
+        let txs = vec![fees(59_999, "1", "1", "1"), fees(60_000, "7", "7", "7")];
+
+        let buckets = fee_buckets(&txs, 60_000).unwrap();
+        let starts = buckets
+            .iter()
+            .map(|b| (b.bucket_start_ms, b.gas_price.p99))
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![(0, 1), (60_000, 7)]);
+    }
+
+    #[test]
+    fn test_type_counts() {
+        let txs = vec![
+            fees(1, "7", "7", "7").with_tx_type(2),
+            fees(2, "7", "1", "7").with_tx_type(0),
+            fees(3, "7", "1", "7").with_tx_type(2),
+            fees(4, "7", "1", "7").with_tx_type(3),
+            // files without txType
+            fees(5, "7", "7", "7"),
+            fees(6, "7", "1", "7"),
+            fees(7, "7", "1", "7"),
+        ];
+
+        let buckets = fee_buckets(&txs, 60_000).unwrap();
+        assert_eq!(
+            buckets[0].type_counts,
+            BTreeMap::from([(0, 1), (2, 2), (3, 1)])
+        );
+        assert_eq!(buckets[0].legacy_count, 1);
+        assert_eq!(buckets[0].dynamic_fee_count, 2);
+
+        let df = fee_buckets_dataframe(&buckets).unwrap();
+        let count = |name: &str| df.column(name).unwrap().u64().unwrap().get(0);
+        assert_eq!(count("type0Count"), Some(1));
+        assert_eq!(count("type2Count"), Some(2));
+        assert_eq!(count("type3Count"), Some(1));
+        assert_eq!(count("legacyCount"), Some(1));
+        assert_eq!(count("dynamicFeeCount"), Some(2));
+        assert!(df.column("type1Count").is_err());
+    }
+
+    #[test]
+    fn test_invalid_bucket() {
+        let txs = vec![fees(1, "7", "7", "7")];
+
+        assert!(matches!(
+            fee_buckets(&txs, 0),
+            Err(FeeStatsError::InvalidBucket(0))
+        ));
+    }
+
+    #[test]
+    fn test_total_value() {
+        let txs = vec![
+            tx(1, "0x01").with_value("20000000000000000000"),
+            tx(2, "0x02").with_value("5"),
+            tx(3, "0x03").with_value("not a number"),
+        ];
+
+        let buckets = fee_buckets(&txs, 60_000).unwrap();
+        assert_eq!(buckets[0].total_value, 20_000_000_000_000_000_005);
+    }
+
+    #[test]
+    fn test_fee_buckets_dataframe() {
+        let txs = vec![
+            fees(1, "10", "1", "10"),
+            tx(60_001, "0x02").with_value("20000000000000000000"),
+        ];
+
+        let df = fee_buckets_dataframe(&fee_buckets(&txs, 60_000).unwrap()).unwrap();
+        assert_eq!(df.shape(), (2, 17));
+        assert_eq!(
+            df.column("totalValue").unwrap().str().unwrap().get(1),
+            Some("20000000000000000000")
+        );
+    }
+}
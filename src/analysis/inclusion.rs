@@ -0,0 +1,494 @@
+use crate::TransactionRangeError;
+use crate::{get_transaction_data, naive_datetime_from_millis, TimeRange, TransactionData};
+use polars::error::PolarsError;
+use polars::frame::DataFrame;
+use polars::prelude::{col, DataType, LazyFrame, NamedFrom, Series};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Inclusion {
+    pub block_number: u64,
+    pub block_timestamp_ms: Option<i64>,
+}
+
+/// Transaction hash -> inclusion block index built from local block or receipt files.
+#[derive(Debug, Clone, Default)]
+pub struct BlockInclusions {
+    transactions: HashMap<String, Inclusion>,
+    block_timestamps: HashMap<u64, i64>,
+}
+
+impl BlockInclusions {
+    /// Loads file or all `.json`, `.ndjson`, `.jsonl` and `.parquet` files in a directory.
+    pub fn from_path(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let mut inclusions = Self::default();
+        inclusions.load_path(path)?;
+        Ok(inclusions)
+    }
+
+    pub fn load_path(&mut self, path: impl AsRef<Path>) -> eyre::Result<()> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(path)?
+                .map(|e| e.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.sort();
+            for entry in entries {
+                let supported = entry.extension().is_some_and(|e| {
+                    e == "json" || e == "ndjson" || e == "jsonl" || e == "parquet"
+                });
+                if entry.is_file() && supported {
+                    self.load_path(entry)?;
+                }
+            }
+            return Ok(());
+        }
+
+        tracing::debug!("Loading block data from {}", path.display());
+        if path.extension().is_some_and(|e| e == "parquet") {
+            self.load_parquet(path)
+        } else {
+            self.load_json_rpc(&std::fs::read(path)?)
+        }
+    }
+
+    /// Loads JSON-RPC dumps: `eth_getBlockByNumber` results (with transaction hashes or full
+    /// transactions) and `eth_getTransactionReceipt`/`eth_getBlockReceipts` results.
+    /// Content can be a single JSON value (object, array or JSON-RPC response) or one value per line.
+    pub fn load_json_rpc(&mut self, content: &[u8]) -> eyre::Result<()> {
+        match serde_json::from_slice::<Value>(content) {
+            Ok(value) => self.load_json_value(&value),
+            Err(_) => {
+                for line in content.split(|b| *b == b'\n') {
+                    if line.iter().all(|b| b.is_ascii_whitespace()) {
+                        continue;
+                    }
+                    self.load_json_value(&serde_json::from_slice(line)?);
+                }
+            }
+        }
+        self.fill_block_timestamps();
+        Ok(())
+    }
+
+    fn load_json_value(&mut self, value: &Value) {
+        match value {
+            Value::Array(values) => values.iter().for_each(|v| self.load_json_value(v)),
+            Value::Object(object) => {
+                if let Some(result) = object.get("result") {
+                    self.load_json_value(result);
+                } else if let (Some(number), Some(transactions)) =
+                    (object.get("number"), object.get("transactions"))
+                {
+                    // block
+                    let Some(block_number) = json_u64(number) else {
+                        return;
+                    };
+                    let block_timestamp_ms = object
+                        .get("timestamp")
+                        .and_then(json_u64)
+                        .map(|t| t as i64 * 1000);
+                    if let Some(timestamp_ms) = block_timestamp_ms {
+                        self.block_timestamps.insert(block_number, timestamp_ms);
+                    }
+                    for tx in transactions.as_array().into_iter().flatten() {
+                        let hash = match tx {
+                            Value::String(hash) => Some(hash.as_str()),
+                            tx => tx.get("hash").and_then(Value::as_str),
+                        };
+                        if let Some(hash) = hash {
+                            self.insert(hash, block_number, block_timestamp_ms);
+                        }
+                    }
+                } else if let (Some(hash), Some(number)) =
+                    (object.get("transactionHash"), object.get("blockNumber"))
+                {
+                    // receipt
+                    let (Some(hash), Some(block_number)) = (hash.as_str(), json_u64(number)) else {
+                        return;
+                    };
+                    let block_timestamp_ms = object
+                        .get("blockTimestamp")
+                        .and_then(json_u64)
+                        .map(|t| t as i64 * 1000);
+                    self.insert(hash, block_number, block_timestamp_ms);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Loads parquet with one row per transaction, e.g. exported with cryo.
+    /// Columns: `transaction_hash` or `hash` (string or binary), `block_number` or `blockNumber`
+    /// and optional `block_timestamp` or `timestamp` (unix seconds).
+    pub fn load_parquet(&mut self, path: impl AsRef<Path>) -> eyre::Result<()> {
+        let mut lf = LazyFrame::scan_parquet(path.as_ref(), Default::default())?;
+        let schema = lf.schema()?;
+        let find = |names: &[&str]| {
+            names
+                .iter()
+                .find(|name| schema.contains(name))
+                .map(|name| name.to_string())
+        };
+
+        let hash_column = find(&["transaction_hash", "hash"])
+            .ok_or_else(|| eyre::eyre!("transaction hash column not found"))?;
+        let block_column = find(&["block_number", "blockNumber"])
+            .ok_or_else(|| eyre::eyre!("block number column not found"))?;
+        let timestamp_column = find(&["block_timestamp", "blockTimestamp", "timestamp"]);
+        let columns = [
+            Some(&hash_column),
+            Some(&block_column),
+            timestamp_column.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|name| col(name))
+        .collect::<Vec<_>>();
+        let df = lf.select(columns).collect()?;
+
+        let hashes = df.column(&hash_column)?;
+        let hashes: Vec<Option<String>> = match hashes.dtype() {
+            DataType::Binary => hashes
+                .binary()?
+                .into_iter()
+                .map(|h| h.map(|h| format!("0x{}", hex::encode(h))))
+                .collect(),
+            _ => hashes
+                .str()?
+                .into_iter()
+                .map(|h| h.map(|h| h.to_string()))
+                .collect(),
+        };
+        let blocks = df.column(&block_column)?.cast(&DataType::UInt64)?;
+        let blocks = blocks.u64()?;
+        let timestamps = match &timestamp_column {
+            Some(name) => Some(df.column(name)?.cast(&DataType::Int64)?),
+            None => None,
+        };
+        let timestamps = timestamps.as_ref().map(|t| t.i64()).transpose()?;
+
+        for (i, hash) in hashes.iter().enumerate() {
+            let (Some(hash), Some(block_number)) = (hash, blocks.get(i)) else {
+                continue;
+            };
+            let block_timestamp_ms = timestamps.and_then(|t| t.get(i)).map(|t| t * 1000);
+            self.insert(hash, block_number, block_timestamp_ms);
+        }
+        self.fill_block_timestamps();
+        Ok(())
+    }
+
+    pub fn insert(&mut self, hash: &str, block_number: u64, block_timestamp_ms: Option<i64>) {
+        if let Some(timestamp_ms) = block_timestamp_ms {
+            self.block_timestamps.insert(block_number, timestamp_ms);
+        }
+        self.transactions.insert(
+            hash.to_lowercase(),
+            Inclusion {
+                block_number,
+                block_timestamp_ms,
+            },
+        );
+    }
+
+    // receipts don't have block timestamps, take them from the loaded blocks
+    fn fill_block_timestamps(&mut self) {
+        for inclusion in self.transactions.values_mut() {
+            if inclusion.block_timestamp_ms.is_none() {
+                inclusion.block_timestamp_ms =
+                    self.block_timestamps.get(&inclusion.block_number).copied();
+            }
+        }
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&Inclusion> {
+        self.transactions.get(&hash.to_lowercase())
+    }
+
+    /// Time range from the first to the last loaded block timestamp, `None` if the block data has
+    /// no timestamps.
+    pub fn time_range(&self) -> Option<TimeRange> {
+        let first = self.block_timestamps.values().min()?;
+        let last = self.block_timestamps.values().max()?;
+        Some((*first..=*last).into())
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+}
+
+fn json_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum InclusionStatus {
+    Included,
+    /// not found in the block data although it was first seen in its time range
+    NeverIncluded,
+    /// not found in the block data and first seen outside its time range
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TransactionInclusion {
+    pub hash: String,
+    /// first seen in the mempool
+    pub timestamp_ms: i64,
+    pub status: InclusionStatus,
+    /// `None` if the transaction is not found in the block data
+    pub block_number: Option<u64>,
+    pub block_timestamp_ms: Option<i64>,
+    /// block timestamp - first seen, negative if the transaction was seen after inclusion
+    pub inclusion_delay_ms: Option<i64>,
+}
+
+impl TransactionInclusion {
+    pub fn is_included(&self) -> bool {
+        self.status == InclusionStatus::Included
+    }
+}
+
+/// Joins transaction-data files for the given time range with the block data.
+pub fn inclusion_report(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+    inclusions: &BlockInclusions,
+) -> Result<Vec<TransactionInclusion>, TransactionRangeError> {
+    let transactions = get_transaction_data(data_dir, range)?;
+    Ok(join_inclusions(&transactions, inclusions))
+}
+
+/// Returns one row per transaction hash (earliest sighting) ordered by first seen timestamp.
+/// Transactions missing from the block data are only reported as never included if they were
+/// first seen between the first and the last block, they might be included outside of it otherwise.
+pub fn join_inclusions(
+    transactions: &[TransactionData],
+    inclusions: &BlockInclusions,
+) -> Vec<TransactionInclusion> {
+    let mut first_seen: HashMap<&str, i64> = HashMap::new();
+    for tx in transactions {
+        let timestamp_ms = first_seen.entry(&tx.hash).or_insert(tx.timestamp_ms);
+        *timestamp_ms = (*timestamp_ms).min(tx.timestamp_ms);
+    }
+
+    let block_range = inclusions.time_range();
+    let mut result = first_seen
+        .into_iter()
+        .map(|(hash, timestamp_ms)| {
+            let inclusion = inclusions.get(hash);
+            let block_timestamp_ms = inclusion.and_then(|i| i.block_timestamp_ms);
+            let status = match inclusion {
+                Some(_) => InclusionStatus::Included,
+                None if block_range.is_some_and(|r| r.contains(timestamp_ms)) => {
+                    InclusionStatus::NeverIncluded
+                }
+                None => InclusionStatus::Unknown,
+            };
+            TransactionInclusion {
+                hash: hash.to_string(),
+                timestamp_ms,
+                status,
+                block_number: inclusion.map(|i| i.block_number),
+                block_timestamp_ms,
+                inclusion_delay_ms: block_timestamp_ms.map(|t| t - timestamp_ms),
+            }
+        })
+        .collect::<Vec<_>>();
+    result.sort_by(|a, b| (a.timestamp_ms, &a.hash).cmp(&(b.timestamp_ms, &b.hash)));
+    result
+}
+
+/// Converts the report to a dataframe, `included` is null if the status is unknown.
+pub fn inclusion_dataframe(rows: &[TransactionInclusion]) -> Result<DataFrame, PolarsError> {
+    DataFrame::new(vec![
+        Series::new(
+            "timestamp",
+            rows.iter()
+                .map(|r| naive_datetime_from_millis(r.timestamp_ms))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "hash",
+            rows.iter().map(|r| r.hash.as_str()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "included",
+            rows.iter()
+                .map(|r| match r.status {
+                    InclusionStatus::Included => Some(true),
+                    InclusionStatus::NeverIncluded => Some(false),
+                    InclusionStatus::Unknown => None,
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "blockNumber",
+            rows.iter().map(|r| r.block_number).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "blockTimestamp",
+            rows.iter()
+                .map(|r| r.block_timestamp_ms.and_then(naive_datetime_from_millis))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "inclusionDelayMs",
+            rows.iter()
+                .map(|r| r.inclusion_delay_ms)
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tx;
+    use polars::prelude::ParquetWriter;
+
+    // block 16 at 3s with two transactions and the receipt of a third one
+    fn block_16() -> BlockInclusions {
+        let blocks = r#"{"jsonrpc":"2.0","id":1,"result":{"number":"0x10","timestamp":"0x3","transactions":["0xAA01",{"hash":"0xaa02"}]}}
+{"transactionHash":"0xaa03","blockNumber":"0x10"}
+"#;
+        let mut inclusions = BlockInclusions::default();
+        inclusions.load_json_rpc(blocks.as_bytes()).unwrap();
+        inclusions
+    }
+
+    #[test]
+    fn test_load_json_rpc() {
+        let inclusions = block_16();
+        assert_eq!(inclusions.len(), 3);
+        let included = Some(&Inclusion {
+            block_number: 16,
+            block_timestamp_ms: Some(3_000),
+        });
+        assert_eq!(inclusions.get("0xaa01"), included);
+        assert_eq!(inclusions.get("0xAA02"), included);
+        // receipts take the timestamp of the loaded block
+        assert_eq!(inclusions.get("0xaa03"), included);
+    }
+
+    #[test]
+    fn test_load_parquet() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transactions.parquet");
+        let mut df = DataFrame::new(vec![
+            Series::new("transaction_hash", ["0xAA01", "0xaa02"]),
+            // not read
+            Series::new("input", ["0x", "0x"]),
+            Series::new("block_number", [16u32, 17]),
+            Series::new("block_timestamp", [3i64, 15]),
+        ])
+        .unwrap();
+        ParquetWriter::new(std::fs::File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+
+        let inclusions = BlockInclusions::from_path(dir.path()).unwrap();
+        assert_eq!(
+            inclusions.get("0xaa01"),
+            Some(&Inclusion {
+                block_number: 16,
+                block_timestamp_ms: Some(3_000),
+            })
+        );
+        assert_eq!(inclusions.get("0xaa02").unwrap().block_number, 17);
+    }
+
+    #[test]
+    fn test_inclusion_delay() {
+        let rows = join_inclusions(&[tx(1_000, "0xaa01"), tx(3_500, "0xaa02")], &block_16());
+        let delays = rows
+            .iter()
+            .map(|r| (r.hash.as_str(), r.block_number, r.inclusion_delay_ms))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![
+                ("0xaa01", Some(16), Some(2_000)),
+                // seen after inclusion
+                ("0xaa02", Some(16), Some(-500))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_never_included() {
+        let mut inclusions = block_16();
+        inclusions.insert("0xaa05", 17, Some(15_000));
+
+        let rows = join_inclusions(&[tx(2_500, "0xaa04"), tx(4_000, "0xaa06")], &inclusions);
+        let statuses = rows
+            .iter()
+            .map(|r| (r.hash.as_str(), r.status, r.inclusion_delay_ms))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![
+                // seen before the first block
+                ("0xaa04", InclusionStatus::Unknown, None),
+                ("0xaa06", InclusionStatus::NeverIncluded, None),
+            ]
+        );
+
+        let df = inclusion_dataframe(&rows).unwrap();
+        let included = df.column("included").unwrap().bool().unwrap();
+        assert_eq!(included.get(0), None);
+        assert_eq!(included.get(1), Some(false));
+    }
+
+    #[test]
+    fn test_unknown_without_block_timestamps() {
+        let mut inclusions = BlockInclusions::default();
+        inclusions.insert("0xaa01", 16, None);
+
+        let rows = join_inclusions(&[tx(1_000, "0xaa01"), tx(1_000, "0xaa02")], &inclusions);
+        let statuses = rows.iter().map(|r| r.status).collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![InclusionStatus::Included, InclusionStatus::Unknown]
+        );
+    }
+
+    #[test]
+    fn test_inclusion_of_earliest_sighting() {
+        let rows = join_inclusions(
+            &[
+                tx(2_500, "0xaa03"),
+                tx(2_000, "0xaa03"),
+                tx(1_000, "0xaa01"),
+            ],
+            &block_16(),
+        );
+        let result = rows
+            .iter()
+            .map(|r| (r.hash.as_str(), r.timestamp_ms, r.inclusion_delay_ms))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                ("0xaa01", 1_000, Some(2_000)),
+                ("0xaa03", 2_000, Some(1_000))
+            ]
+        );
+    }
+}
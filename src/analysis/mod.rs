@@ -0,0 +1,13 @@
+// Analyses built on top of the downloaded files.
+
+mod fees;
+mod inclusion;
+mod nonces;
+mod replacements;
+mod selectors;
+
+pub use fees::*;
+pub use inclusion::*;
+pub use nonces::*;
+pub use replacements::*;
+pub use selectors::*;
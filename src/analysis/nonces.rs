@@ -0,0 +1,295 @@
+use crate::analysis::find_replacement_chains;
+use crate::{get_transaction_data, TimeRange, TransactionData, TransactionRangeError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Nonces that were never observed between two nonces of the same sender.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct NonceGap {
+    pub from: String,
+    /// last observed nonce before the gap
+    pub nonce: u64,
+    /// first observed nonce after the gap
+    pub next_nonce: u64,
+    pub missing: u64,
+    /// when the transaction with `next_nonce` was first seen
+    pub next_nonce_timestamp_ms: i64,
+}
+
+/// Transaction that stayed in the mempool for a long time, either until it was replaced or, if it
+/// was never replaced, until it was included or the end of the time window.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct StuckTransaction {
+    pub from: String,
+    pub nonce: u64,
+    pub hash: String,
+    pub timestamp_ms: i64,
+    /// none if the transaction was included late or still pending at the end of the window
+    pub replaced_by: Option<String>,
+    pub pending_ms: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NonceReport {
+    pub senders: usize,
+    pub gaps: Vec<NonceGap>,
+    pub stuck: Vec<StuckTransaction>,
+}
+
+/// Builds nonce report for transaction-data files in the given time range.
+/// Transactions are reported as stuck if they were replaced after more than `stuck_after_ms`, or
+/// if they were never replaced, no later nonce of the sender was seen and they were included more
+/// than `stuck_after_ms` after they were first seen, or not included and pending for more than
+/// `stuck_after_ms` at the end of the range.
+///
+/// Unreplaced transactions are only reported when the files record inclusion, i.e. at least one
+/// transaction has an inclusion block or delay, older files can't tell pending from included.
+pub fn nonce_gaps(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+    stuck_after_ms: i64,
+) -> Result<NonceReport, TransactionRangeError> {
+    let range = range.into();
+    let transactions = get_transaction_data(data_dir, range)?;
+    Ok(nonce_report(&transactions, range.end_ms(), stuck_after_ms))
+}
+
+/// Nonce report of the transactions of a window ending at `window_end_ms`, see [`nonce_gaps`].
+pub fn nonce_report(
+    transactions: &[TransactionData],
+    window_end_ms: i64,
+    stuck_after_ms: i64,
+) -> NonceReport {
+    // nonce -> first seen timestamp, last transaction seen and included transaction for each sender
+    let mut senders: HashMap<&str, BTreeMap<u64, NonceTransactions>> = HashMap::new();
+    for tx in transactions {
+        let Ok(nonce) = tx.nonce.parse::<u64>() else {
+            continue;
+        };
+        let (first_seen, last, included) = senders
+            .entry(&tx.from)
+            .or_default()
+            .entry(nonce)
+            .or_insert((tx.timestamp_ms, tx, None));
+        *first_seen = (*first_seen).min(tx.timestamp_ms);
+        if tx.timestamp_ms > last.timestamp_ms {
+            *last = tx;
+        }
+        if is_included(tx) {
+            *included = Some(tx);
+        }
+    }
+    let inclusion_recorded = transactions.iter().any(is_included);
+
+    let mut gaps = Vec::new();
+    for (from, nonces) in &senders {
+        let mut nonces = nonces.iter();
+        let Some((mut prev, _)) = nonces.next() else {
+            continue;
+        };
+        for (nonce, (timestamp_ms, _, _)) in nonces {
+            if nonce - prev > 1 {
+                gaps.push(NonceGap {
+                    from: from.to_string(),
+                    nonce: *prev,
+                    next_nonce: *nonce,
+                    missing: nonce - prev - 1,
+                    next_nonce_timestamp_ms: *timestamp_ms,
+                });
+            }
+            prev = nonce;
+        }
+    }
+    gaps.sort_by(|a, b| (&a.from, a.nonce).cmp(&(&b.from, b.nonce)));
+
+    let mut stuck = Vec::new();
+    for chain in find_replacement_chains(transactions) {
+        for pair in chain.entries.windows(2) {
+            let pending_ms = pair[1].timestamp_ms - pair[0].timestamp_ms;
+            if pending_ms >= stuck_after_ms {
+                stuck.push(StuckTransaction {
+                    from: chain.from.clone(),
+                    nonce: chain.nonce,
+                    hash: pair[0].hash.clone(),
+                    timestamp_ms: pair[0].timestamp_ms,
+                    replaced_by: Some(pair[1].hash.clone()),
+                    pending_ms,
+                });
+            }
+        }
+    }
+    // the last transaction of the highest nonce of a sender was neither replaced nor followed
+    for (from, nonces) in senders.iter().filter(|_| inclusion_recorded) {
+        let Some((nonce, (_, last, included))) = nonces.last_key_value() else {
+            continue;
+        };
+        let pending_ms = match included {
+            // another transaction of the nonce was included, the last one was a failed replacement
+            Some(included) if included.hash != last.hash => continue,
+            Some(included) => match inclusion_delay_ms(included) {
+                Some(delay_ms) => delay_ms,
+                None => continue,
+            },
+            None => window_end_ms - last.timestamp_ms,
+        };
+        if pending_ms >= stuck_after_ms {
+            stuck.push(StuckTransaction {
+                from: from.to_string(),
+                nonce: *nonce,
+                hash: last.hash.clone(),
+                timestamp_ms: last.timestamp_ms,
+                replaced_by: None,
+                pending_ms,
+            });
+        }
+    }
+    stuck.sort_by(|a, b| {
+        (&a.from, a.nonce, a.timestamp_ms).cmp(&(&b.from, b.nonce, b.timestamp_ms))
+    });
+
+    NonceReport {
+        senders: senders.len(),
+        gaps,
+        stuck,
+    }
+}
+
+type NonceTransactions<'a> = (i64, &'a TransactionData, Option<&'a TransactionData>);
+
+fn is_included(tx: &TransactionData) -> bool {
+    tx.included_at_block_height.is_some()
+        || tx.included_block_timestamp_ms.is_some()
+        || tx.inclusion_delay_ms.is_some()
+}
+
+fn inclusion_delay_ms(tx: &TransactionData) -> Option<i64> {
+    tx.inclusion_delay_ms.or_else(|| {
+        tx.included_block_timestamp_ms
+            .map(|included_ms| included_ms - tx.timestamp_ms)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tx;
+
+    fn sent(timestamp_ms: i64, hash: &str, from: &str, nonce: u64) -> TransactionData {
+        tx(timestamp_ms, hash).with_from(from).with_nonce(nonce)
+    }
+
+    #[test]
+    fn test_nonce_gaps() {
+        let txs = vec![
+            sent(1_000, "0x01", "0xaa", 1),
+            sent(2_000, "0x02", "0xaa", 2),
+            sent(3_000, "0x05", "0xaa", 5),
+            sent(4_000, "0x06", "0xaa", 6),
+            sent(5_000, "0x10", "0xcc", 10),
+            sent(6_000, "0x11", "0xcc", 11),
+        ];
+
+        let report = nonce_report(&txs, 10_000, 60_000);
+        assert_eq!(report.senders, 2);
+        assert_eq!(
+            report.gaps,
+            vec![NonceGap {
+                from: "0xaa".to_string(),
+                nonce: 2,
+                next_nonce: 5,
+                missing: 2,
+                next_nonce_timestamp_ms: 3_000,
+            }]
+        );
+        assert!(report.stuck.is_empty());
+    }
+
+    #[test]
+    fn test_stuck_until_replaced() {
+        let txs = vec![
+            sent(4_000, "0x06", "0xaa", 6),
+            // replaced after 10 minutes
+            sent(604_000, "0x06b", "0xaa", 6),
+            sent(605_000, "0x07", "0xaa", 7),
+        ];
+
+        let report = nonce_report(&txs, 606_000, 60_000);
+        assert_eq!(
+            report.stuck,
+            vec![StuckTransaction {
+                from: "0xaa".to_string(),
+                nonce: 6,
+                hash: "0x06".to_string(),
+                timestamp_ms: 4_000,
+                replaced_by: Some("0x06b".to_string()),
+                pending_ms: 600_000,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_stuck_until_end_of_window() {
+        let txs = vec![
+            // followed by the next nonce
+            sent(1_000, "0x01", "0xaa", 1).with_included(100, 13_000),
+            sent(2_000, "0x02", "0xaa", 2),
+            // replaced, the replacement is never followed
+            sent(3_000, "0x10", "0xcc", 10),
+            sent(4_000, "0x10b", "0xcc", 10),
+            // included shortly after it was seen
+            sent(5_000, "0x20", "0xdd", 20).with_included(101, 17_000),
+            // seen shortly before the end of the window
+            sent(650_000, "0x30", "0xee", 30),
+        ];
+
+        let report = nonce_report(&txs, 664_000, 60_000);
+        let stuck = report
+            .stuck
+            .iter()
+            .map(|s| (s.hash.as_str(), s.replaced_by.as_deref(), s.pending_ms))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stuck,
+            vec![("0x02", None, 662_000), ("0x10b", None, 660_000)]
+        );
+    }
+
+    #[test]
+    fn test_stuck_until_included() {
+        let txs = vec![
+            // included 2 minutes after it was seen
+            sent(1_000, "0x01", "0xaa", 1).with_included(100, 121_000),
+            // the replacement failed, the original transaction was included in time
+            sent(2_000, "0x10", "0xcc", 10).with_included(101, 14_000),
+            sent(3_000, "0x10b", "0xcc", 10),
+        ];
+
+        let report = nonce_report(&txs, 664_000, 60_000);
+        let stuck = report
+            .stuck
+            .iter()
+            .map(|s| (s.hash.as_str(), s.replaced_by.as_deref(), s.pending_ms))
+            .collect::<Vec<_>>();
+        assert_eq!(stuck, vec![("0x01", None, 120_000)]);
+    }
+
+    #[test]
+    fn test_stuck_without_inclusion_data() {
+        let txs = vec![
+            sent(1_000, "0x01", "0xaa", 1),
+            sent(4_000, "0x10", "0xcc", 10),
+            // replaced after 10 minutes
+            sent(604_000, "0x10b", "0xcc", 10),
+        ];
+
+        // only replacements are reported when the files don't record inclusion
+        let report = nonce_report(&txs, 664_000, 60_000);
+        let stuck = report
+            .stuck
+            .iter()
+            .map(|s| (s.hash.as_str(), s.replaced_by.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(stuck, vec![("0x10", Some("0x10b"))]);
+    }
+}
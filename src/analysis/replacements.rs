@@ -0,0 +1,236 @@
+use crate::{get_transaction_data, TimeRange, TransactionData, TransactionRangeError};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ReplacementKind {
+    /// first transaction seen for the (from, nonce) pair
+    Original,
+    /// replacement that pays more for the same nonce
+    SpeedUp,
+    /// replacement that sends zero value to the sender itself
+    Cancellation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplacementEntry {
+    pub timestamp_ms: i64,
+    pub hash: String,
+    pub to: String,
+    pub value: String,
+    pub gas_fee_cap: Option<u128>,
+    pub gas_tip_cap: Option<u128>,
+    pub kind: ReplacementKind,
+    /// fee cap increase over the previous entry of the chain, in percent
+    pub fee_cap_bump_pct: Option<f64>,
+    /// tip cap increase over the previous entry of the chain, in percent
+    pub tip_cap_bump_pct: Option<f64>,
+}
+
+/// All transactions seen for the same (from, nonce) pair ordered by timestamp.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplacementChain {
+    pub from: String,
+    pub nonce: u64,
+    pub entries: Vec<ReplacementEntry>,
+}
+
+impl ReplacementChain {
+    pub fn is_cancelled(&self) -> bool {
+        self.entries
+            .last()
+            .map(|e| e.kind == ReplacementKind::Cancellation)
+            .unwrap_or(false)
+    }
+}
+
+/// Finds replacement chains in transaction-data files for the given time range.
+pub fn get_replacement_chains(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+) -> Result<Vec<ReplacementChain>, TransactionRangeError> {
+    let transactions = get_transaction_data(data_dir, range)?;
+    Ok(find_replacement_chains(&transactions))
+}
+
+/// Groups transactions by (from, nonce) and returns groups that have more than one transaction.
+/// Chains are ordered by timestamp of the first transaction.
+pub fn find_replacement_chains(transactions: &[TransactionData]) -> Vec<ReplacementChain> {
+    let mut groups: HashMap<(&str, u64), Vec<&TransactionData>> = HashMap::new();
+    for tx in transactions {
+        let nonce = match tx.nonce.parse::<u64>() {
+            Ok(nonce) => nonce,
+            Err(_) => {
+                tracing::debug!("Invalid nonce {:?} for tx {}", tx.nonce, tx.hash);
+                continue;
+            }
+        };
+        groups.entry((&tx.from, nonce)).or_default().push(tx);
+    }
+
+    let mut chains = Vec::new();
+    for ((from, nonce), mut txs) in groups {
+        txs.sort_by_key(|tx| tx.timestamp_ms);
+
+        // the same transaction can be present in the files for two days
+        let mut seen = HashSet::new();
+        txs.retain(|tx| seen.insert(&tx.hash));
+
+        if txs.len() < 2 {
+            continue;
+        }
+
+        let mut entries: Vec<ReplacementEntry> = Vec::with_capacity(txs.len());
+        for tx in txs {
+            let gas_fee_cap = parse_fee(&tx.gas_fee_cap).or_else(|| parse_fee(&tx.gas_price));
+            let gas_tip_cap = parse_fee(&tx.gas_tip_cap).or_else(|| parse_fee(&tx.gas_price));
+
+            let (kind, fee_cap_bump_pct, tip_cap_bump_pct) = match entries.last() {
+                None => (ReplacementKind::Original, None, None),
+                Some(prev) => {
+                    let kind = if is_cancellation(tx) {
+                        ReplacementKind::Cancellation
+                    } else {
+                        ReplacementKind::SpeedUp
+                    };
+                    (
+                        kind,
+                        bump_pct(prev.gas_fee_cap, gas_fee_cap),
+                        bump_pct(prev.gas_tip_cap, gas_tip_cap),
+                    )
+                }
+            };
+
+            entries.push(ReplacementEntry {
+                timestamp_ms: tx.timestamp_ms,
+                hash: tx.hash.clone(),
+                to: tx.to.clone(),
+                value: tx.value.clone(),
+                gas_fee_cap,
+                gas_tip_cap,
+                kind,
+                fee_cap_bump_pct,
+                tip_cap_bump_pct,
+            });
+        }
+
+        chains.push(ReplacementChain {
+            from: from.to_string(),
+            nonce,
+            entries,
+        });
+    }
+
+    chains.sort_by(|a, b| {
+        (a.entries[0].timestamp_ms, &a.from, a.nonce).cmp(&(
+            b.entries[0].timestamp_ms,
+            &b.from,
+            b.nonce,
+        ))
+    });
+    chains
+}
+
+fn is_cancellation(tx: &TransactionData) -> bool {
+    tx.to.eq_ignore_ascii_case(&tx.from) && parse_fee(&tx.value) == Some(0)
+}
+
+fn parse_fee(value: &str) -> Option<u128> {
+    value.parse().ok()
+}
+
+fn bump_pct(prev: Option<u128>, next: Option<u128>) -> Option<f64> {
+    match (prev, next) {
+        (Some(prev), Some(next)) if prev > 0 => {
+            Some((next as f64 - prev as f64) / prev as f64 * 100.0)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tx;
+
+    // transfer from 0xaa with the given nonce and fee cap
+    fn transfer(timestamp_ms: i64, hash: &str, nonce: u64, fee_cap: &str) -> TransactionData {
+        tx(timestamp_ms, hash)
+            .with_value("5")
+            .with_nonce(nonce)
+            .with_gas_price(fee_cap)
+            .with_gas_fee_cap(fee_cap)
+    }
+
+    #[test]
+    fn test_chains_group_sender_and_nonce() {
+        let txs = vec![
+            transfer(1, "0x01", 7, "1000"),
+            transfer(2, "0x02", 7, "1200"),
+            transfer(3, "0x03", 8, "1000"),
+            transfer(4, "0x04", 7, "1000").with_from("0xcc"),
+        ];
+
+        let chains = find_replacement_chains(&txs);
+        assert_eq!(chains.len(), 1);
+        assert_eq!((chains[0].from.as_str(), chains[0].nonce), ("0xaa", 7));
+        let hashes = chains[0]
+            .entries
+            .iter()
+            .map(|e| e.hash.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(hashes, vec!["0x01", "0x02"]);
+    }
+
+    #[test]
+    fn test_chains_ignore_rows_of_the_next_day_file() {
+        let txs = vec![
+            transfer(1, "0x01", 7, "1000"),
+            transfer(1, "0x01", 7, "1000"),
+        ];
+        assert!(find_replacement_chains(&txs).is_empty());
+    }
+
+    #[test]
+    fn test_replacement_kinds() {
+        let txs = vec![
+            tx(3, "0x03").with_to("0xaa").with_nonce(7),
+            transfer(1, "0x01", 7, "1000"),
+            transfer(2, "0x02", 7, "1200"),
+        ];
+
+        let chains = find_replacement_chains(&txs);
+        assert!(chains[0].is_cancelled());
+        let kinds = chains[0].entries.iter().map(|e| e.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ReplacementKind::Original,
+                ReplacementKind::SpeedUp,
+                ReplacementKind::Cancellation
+            ]
+        );
+
+        let speed_up = find_replacement_chains(&txs[1..]);
+        assert!(!speed_up[0].is_cancelled());
+    }
+
+    #[test]
+    fn test_fee_bumps() {
+        let txs = vec![
+            transfer(1, "0x01", 7, "1000"),
+            transfer(2, "0x02", 7, "1200"),
+            transfer(3, "0x03", 7, "1320"),
+        ];
+
+        let chain = &find_replacement_chains(&txs)[0];
+        let bumps = chain
+            .entries
+            .iter()
+            .map(|e| e.fee_cap_bump_pct.map(|p| p.round() as i64))
+            .collect::<Vec<_>>();
+        assert_eq!(bumps, vec![None, Some(20), Some(10)]);
+        assert_eq!(chain.entries[1].tip_cap_bump_pct, Some(0.0));
+    }
+}
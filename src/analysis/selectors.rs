@@ -0,0 +1,279 @@
+use crate::{get_transaction_data, TimeRange, TransactionData, TransactionRangeError};
+use polars::error::PolarsError;
+use polars::frame::DataFrame;
+use polars::prelude::{NamedFrom, Series};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Map of 4 byte function selectors to function signatures.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureDatabase {
+    signatures: HashMap<String, String>,
+}
+
+// 4byte.directory api format
+#[derive(Deserialize)]
+struct SignatureJsonRecord {
+    #[serde(alias = "selector")]
+    hex_signature: String,
+    #[serde(alias = "signature")]
+    text_signature: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignatureJson {
+    Map(HashMap<String, SignatureJsonValue>),
+    List(Vec<SignatureJsonRecord>),
+    Results { results: Vec<SignatureJsonRecord> },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignatureJsonValue {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl SignatureDatabase {
+    /// Loads signatures from a local file.
+    ///
+    /// Supported formats:
+    /// * `.json` with an object `{"0xa9059cbb": "transfer(address,uint256)"}` (values can be lists),
+    ///   a list of `{"hex_signature": .., "text_signature": ..}` or 4byte.directory api response
+    /// * `.csv` (or any other extension) with `selector,signature` rows, header is optional
+    pub fn from_file(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read(path)?;
+        if path.extension().is_some_and(|e| e == "json") {
+            Self::from_json(&content)
+        } else {
+            Self::from_csv(content.as_slice())
+        }
+    }
+
+    pub fn from_json(content: &[u8]) -> eyre::Result<Self> {
+        let mut db = Self::default();
+        match serde_json::from_slice::<SignatureJson>(content)? {
+            SignatureJson::Map(map) => {
+                for (selector, value) in map {
+                    match value {
+                        SignatureJsonValue::One(signature) => db.insert(&selector, signature),
+                        SignatureJsonValue::Many(signatures) => {
+                            for signature in signatures {
+                                db.insert(&selector, signature);
+                            }
+                        }
+                    }
+                }
+            }
+            SignatureJson::List(records) | SignatureJson::Results { results: records } => {
+                for record in records {
+                    db.insert(&record.hex_signature, record.text_signature);
+                }
+            }
+        }
+        Ok(db)
+    }
+
+    pub fn from_csv(reader: impl std::io::Read) -> eyre::Result<Self> {
+        let mut db = Self::default();
+        let mut csv = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        for record in csv.records() {
+            let record = record?;
+            let (Some(selector), Some(signature)) = (record.get(0), record.get(1)) else {
+                continue;
+            };
+            db.insert(selector, signature.to_string());
+        }
+        Ok(db)
+    }
+
+    // first signature wins on collisions, rows that are not selectors (e.g. header) are skipped
+    fn insert(&mut self, selector: &str, signature: String) {
+        if let Some(selector) = normalize_selector(selector) {
+            self.signatures.entry(selector).or_insert(signature);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// Returns signature for the selector, e.g. `0xa9059cbb` -> `transfer(address,uint256)`.
+    pub fn function_name(&self, selector: &str) -> Option<&str> {
+        self.signatures
+            .get(&normalize_selector(selector)?)
+            .map(|s| s.as_str())
+    }
+
+    /// Adds `functionName` column to a dataframe with `data4Bytes` column.
+    pub fn annotate(&self, mut df: DataFrame) -> Result<DataFrame, PolarsError> {
+        let names = df
+            .column("data4Bytes")?
+            .str()?
+            .into_iter()
+            .map(|selector| selector.and_then(|s| self.function_name(s)))
+            .collect::<Vec<_>>();
+        df.with_column(Series::new("functionName", names))?;
+        Ok(df)
+    }
+}
+
+// lowercase 0x-prefixed 4 bytes
+fn normalize_selector(selector: &str) -> Option<String> {
+    let selector = selector.trim();
+    let hex = selector
+        .strip_prefix("0x")
+        .or_else(|| selector.strip_prefix("0X"))
+        .unwrap_or(selector);
+    if hex.len() == 8 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(format!("0x{}", hex.to_lowercase()))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelectorStats {
+    /// empty for transactions without calldata
+    pub selector: String,
+    pub function_name: Option<String>,
+    pub count: u64,
+    /// share of all transactions in the range
+    pub share: f64,
+}
+
+/// Counts transactions per function selector in transaction-data files for the given time range.
+pub fn selector_stats(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+    signatures: &SignatureDatabase,
+) -> Result<Vec<SelectorStats>, TransactionRangeError> {
+    let transactions = get_transaction_data(data_dir, range)?;
+    Ok(count_selectors(&transactions, signatures))
+}
+
+/// Returns selector counts sorted by count (descending).
+pub fn count_selectors(
+    transactions: &[TransactionData],
+    signatures: &SignatureDatabase,
+) -> Vec<SelectorStats> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for tx in transactions {
+        let selector = normalize_selector(&tx.data_4bytes).unwrap_or_default();
+        *counts.entry(selector).or_default() += 1;
+    }
+
+    let total = transactions.len().max(1) as f64;
+    let mut stats = counts
+        .into_iter()
+        .map(|(selector, count)| SelectorStats {
+            function_name: signatures.function_name(&selector).map(|s| s.to_string()),
+            selector,
+            count,
+            share: count as f64 / total,
+        })
+        .collect::<Vec<_>>();
+    stats.sort_by(|a, b| b.count.cmp(&a.count).then(a.selector.cmp(&b.selector)));
+    stats
+}
+
+pub fn selector_stats_dataframe(stats: &[SelectorStats]) -> Result<DataFrame, PolarsError> {
+    DataFrame::new(vec![
+        Series::new(
+            "selector",
+            stats
+                .iter()
+                .map(|s| s.selector.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "functionName",
+            stats
+                .iter()
+                .map(|s| s.function_name.as_deref())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new("count", stats.iter().map(|s| s.count).collect::<Vec<_>>()),
+        Series::new("share", stats.iter().map(|s| s.share).collect::<Vec<_>>()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tx;
+
+    fn transfer_db() -> SignatureDatabase {
+        let csv = "selector,signature\n0xa9059cbb,\"transfer(address,uint256)\"\n095EA7B3,\"approve(address,uint256)\"\n";
+        SignatureDatabase::from_csv(csv.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_signature_database_from_csv() {
+        let db = transfer_db();
+        assert_eq!(db.len(), 2);
+        assert_eq!(
+            db.function_name("0xA9059CBB"),
+            Some("transfer(address,uint256)")
+        );
+        assert_eq!(
+            db.function_name("0x095ea7b3"),
+            Some("approve(address,uint256)")
+        );
+    }
+
+    #[test]
+    fn test_signature_database_from_json() {
+        let json = r#"{"results": [{"hex_signature": "0xa9059cbb", "text_signature": "transfer(address,uint256)"}]}"#;
+        let db = SignatureDatabase::from_json(json.as_bytes()).unwrap();
+        assert_eq!(
+            db.function_name("0xa9059cbb"),
+            Some("transfer(address,uint256)")
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let df = DataFrame::new(vec![Series::new(
+            "data4Bytes",
+            vec!["0xa9059cbb", "", "0x12345678"],
+        )])
+        .unwrap();
+        let df = transfer_db().annotate(df).unwrap();
+        let names = df.column("functionName").unwrap().str().unwrap();
+        assert_eq!(
+            names.into_iter().collect::<Vec<_>>(),
+            vec![Some("transfer(address,uint256)"), None, None]
+        );
+    }
+
+    #[test]
+    fn test_count_selectors() {
+        let txs = vec![
+            tx(1, "0x01").with_data_4bytes("0xa9059cbb"),
+            tx(2, "0x02"),
+            tx(3, "0x03").with_data_4bytes("0xa9059cbb"),
+        ];
+
+        let stats = count_selectors(&txs, &transfer_db());
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].selector, "0xa9059cbb");
+        assert_eq!(stats[0].count, 2);
+        assert_eq!(
+            stats[0].function_name.as_deref(),
+            Some("transfer(address,uint256)")
+        );
+        assert_eq!(stats[1].selector, "");
+        assert!((stats[1].share - 1.0 / 3.0).abs() < 1e-9);
+    }
+}
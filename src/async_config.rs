@@ -0,0 +1,426 @@
+use crate::download::{parse_day_list, parse_month_list, retry_delay};
+use crate::progress::{BytesProgress, FileProgress, ProgressEvent, ProgressRead};
+use crate::reader::{
+    day_paths, days_in_range, merge_sorted, read_raw_transactions_day, read_transaction_data_day,
+};
+use crate::{
+    get_month, path_raw, raw_file_name, Config, ConversionReport, DataDir, DataKind,
+    RawTransaction, TimeRange, TransactionData, TransactionRangeError,
+};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Async version of [`Config`]. Files are downloaded with reqwest (or the blocking fetcher of the
+/// config if set), conversion and parquet reads run on the tokio blocking thread pool.
+#[derive(Clone)]
+pub struct AsyncConfig {
+    config: Arc<Config>,
+    client: reqwest::Client,
+}
+
+impl AsyncConfig {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config: Arc::new(config),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Uses the client for requests, e.g. one with default authentication headers.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub async fn get_month_list(&self) -> eyre::Result<Vec<String>> {
+        if self.is_local() {
+            let config = self.config.clone();
+            return tokio::task::spawn_blocking(move || config.get_month_list()).await?;
+        }
+
+        let url = format!("{}/index.html", self.config.month_index_base_url());
+        let html = self.fetch_url(&url, None).await?;
+        let result = parse_month_list(&String::from_utf8_lossy(&html));
+        if result.is_empty() {
+            Err(eyre::eyre!("failed to get month list"))
+        } else {
+            Ok(result)
+        }
+    }
+
+    pub async fn get_day_list(&self, month: &str) -> eyre::Result<Vec<String>> {
+        if self.is_local() {
+            let config = self.config.clone();
+            let month = month.to_string();
+            return tokio::task::spawn_blocking(move || config.get_day_list(&month)).await?;
+        }
+
+        let html = self.fetch(&format!("{}/index.html", month), None).await?;
+        let result = parse_day_list(&String::from_utf8_lossy(&html));
+        if result.is_empty() {
+            Err(eyre::eyre!("failed to get day list"))
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Downloads and converts sourcelog file, returns `None` if the file already exists.
+    pub async fn download_sourcelog_file(
+        &self,
+        day: &str,
+    ) -> eyre::Result<Option<ConversionReport>> {
+        self.download_file(DataKind::Sourcelog, day).await
+    }
+
+    /// Downloads and converts transaction-data file, returns `None` if the file already exists.
+    pub async fn download_transaction_data_file(
+        &self,
+        day: &str,
+    ) -> eyre::Result<Option<ConversionReport>> {
+        self.download_file(DataKind::TransactionData, day).await
+    }
+
+    /// Downloads transaction file, returns `None` if the file already exists.
+    pub async fn download_transaction_file(
+        &self,
+        day: &str,
+    ) -> eyre::Result<Option<ConversionReport>> {
+        self.download_file(DataKind::Transactions, day).await
+    }
+
+    /// Downloads and converts file of the kind, returns `None` if the file already exists.
+    pub async fn download_file(
+        &self,
+        kind: DataKind,
+        day: &str,
+    ) -> eyre::Result<Option<ConversionReport>> {
+        info!("Downloading {} file for {}", kind, day);
+
+        DataDir::init(&self.config.data_dir)?;
+        let file_path = kind.path(&self.config.data_dir, day);
+        // waiting for the lock blocks
+        let config = self.config.clone();
+        let lock_path = file_path.clone();
+        let Some(_lock) =
+            tokio::task::spawn_blocking(move || config.lock_file(&lock_path)).await??
+        else {
+            return Ok(None);
+        };
+
+        let result = self.download_raw(kind, day, &file_path).await;
+        FileProgress::new(self.config.reporter(), &file_path)
+            .report_result(result)
+            .map(Some)
+    }
+
+    async fn download_raw(
+        &self,
+        kind: DataKind,
+        day: &str,
+        file_path: &Path,
+    ) -> eyre::Result<ConversionReport> {
+        let raw = self
+            .fetch_raw_file(day, &raw_file_name(kind, day), file_path)
+            .await?;
+
+        let config = self.config.clone();
+        let file_path = file_path.to_path_buf();
+        tokio::task::spawn_blocking(move || config.convert(kind, Cursor::new(raw), &file_path))
+            .await?
+    }
+
+    /// Raw transactions in the time range sorted by timestamp, day files are read one at a time.
+    pub fn raw_transactions(
+        &self,
+        range: impl Into<TimeRange>,
+    ) -> Result<
+        BoxStream<'static, Result<RawTransaction, TransactionRangeError>>,
+        TransactionRangeError,
+    > {
+        let range = range.into();
+        let paths = self.day_paths(DataKind::Transactions, &range)?;
+        Ok(sorted_day_stream(
+            paths,
+            move |path| read_raw_transactions_day(path, &range),
+            |r| r.timestamp_ms,
+        ))
+    }
+
+    /// Transaction data in the time range sorted by timestamp, day files are read one at a time.
+    pub fn transaction_data(
+        &self,
+        range: impl Into<TimeRange>,
+    ) -> Result<
+        BoxStream<'static, Result<TransactionData, TransactionRangeError>>,
+        TransactionRangeError,
+    > {
+        let range = range.into();
+        let paths = self.day_paths(DataKind::TransactionData, &range)?;
+        Ok(sorted_day_stream(
+            paths,
+            move |path| read_transaction_data_day(path, &range),
+            |r| r.timestamp_ms,
+        ))
+    }
+
+    // day files of the range, all of them must exist
+    fn day_paths(
+        &self,
+        kind: DataKind,
+        range: &TimeRange,
+    ) -> Result<Vec<PathBuf>, TransactionRangeError> {
+        let days = days_in_range(range)?;
+        day_paths(&self.config.data_dir, kind, &days)
+    }
+
+    // files are fetched with the blocking fetcher of the config or the local directory
+    fn is_local(&self) -> bool {
+        self.config.fetcher.is_some() || self.config.base_url.starts_with("file://")
+    }
+
+    // received bytes are reported for the data directory file if set
+    async fn fetch(&self, path: &str, file: Option<&Path>) -> eyre::Result<Vec<u8>> {
+        if self.is_local() {
+            let config = self.config.clone();
+            let path = path.to_string();
+            let file = file.map(Path::to_path_buf);
+            return tokio::task::spawn_blocking(move || {
+                let progress = FileProgress::new(
+                    file.as_ref().and(config.reporter()),
+                    file.as_deref().unwrap_or(Path::new("")),
+                );
+                let mut buffer = Vec::new();
+                ProgressRead::new(config.fetch(&path)?, progress).read_to_end(&mut buffer)?;
+                Ok(buffer)
+            })
+            .await?;
+        }
+
+        let url = format!("{}/{}", self.config.base_url.trim_end_matches('/'), path);
+        self.fetch_url(&url, file).await
+    }
+
+    async fn fetch_url(&self, url: &str, file: Option<&Path>) -> eyre::Result<Vec<u8>> {
+        debug!("Fetching {}", url);
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        let mut progress = BytesProgress::new(FileProgress::new(
+            file.and(self.config.reporter()),
+            file.unwrap_or(Path::new("")),
+        ));
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            progress.add(chunk.len());
+            buffer.extend_from_slice(&chunk);
+        }
+        progress.finish();
+        Ok(buffer)
+    }
+
+    // same as the blocking version, the raw cache is shared
+    async fn fetch_raw_file(
+        &self,
+        day: &str,
+        file_name: &str,
+        file_path: &Path,
+    ) -> eyre::Result<Vec<u8>> {
+        let raw_path = path_raw(&self.config.data_dir, file_name);
+        if self.config.raw_cache && raw_path.exists() {
+            debug!("Reading cached {}", raw_path.display());
+            return Ok(tokio::fs::read(raw_path).await?);
+        }
+
+        let path = format!("{}/{}", get_month(day), file_name);
+        FileProgress::new(self.config.reporter(), file_path).report(|file| {
+            ProgressEvent::DownloadStarted {
+                file,
+                source: path.clone(),
+            }
+        });
+        let mut attempt = 0;
+        let buffer = loop {
+            match self.fetch(&path, Some(file_path)).await {
+                Ok(buffer) => break buffer,
+                Err(e) if attempt < self.config.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Failed to download {}, retrying ({}/{}): {}",
+                        path, attempt, self.config.retries, e
+                    );
+                    tokio::time::sleep(retry_delay(attempt)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        debug!("Downloaded {} bytes", buffer.len());
+
+        if self.config.raw_cache {
+            write_raw_file(&raw_path, &buffer).await?;
+        }
+        Ok(buffer)
+    }
+}
+
+async fn write_raw_file(raw_path: &Path, buffer: &[u8]) -> eyre::Result<()> {
+    if let Some(parent) = raw_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut tmp_path = raw_path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    tokio::fs::write(&tmp_path, buffer).await?;
+    tokio::fs::rename(&tmp_path, raw_path).await?;
+    Ok(())
+}
+
+// Reads day files in order and yields rows sorted by key. Day files can overlap at the edges so
+// rows of a day are held back until the next day is read and only rows before its first row are
+// yielded.
+fn sorted_day_stream<T: Send + 'static>(
+    paths: Vec<PathBuf>,
+    read: impl Fn(PathBuf) -> Result<Vec<T>, TransactionRangeError> + Send + Sync + 'static,
+    key: impl Fn(&T) -> i64 + Copy + Send + 'static,
+) -> BoxStream<'static, Result<T, TransactionRangeError>> {
+    let read = Arc::new(read);
+    let state = (paths.into_iter(), Vec::new(), false);
+    stream::unfold(state, move |(mut paths, pending, done)| {
+        let read = read.clone();
+        async move {
+            if done {
+                return None;
+            }
+            let Some(path) = paths.next() else {
+                let batch = pending.into_iter().map(Ok).collect::<Vec<_>>();
+                return Some((batch, (paths, Vec::new(), true)));
+            };
+
+            let day = tokio::task::spawn_blocking(move || read(path))
+                .await
+                .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+            let day = match day {
+                Ok(day) => day,
+                Err(e) => return Some((vec![Err(e)], (paths, Vec::new(), true))),
+            };
+
+            let first = day.first().map(key);
+            let mut pending = pending;
+            let split = match first {
+                Some(first) => pending.partition_point(|r| key(r) < first),
+                None => 0,
+            };
+            let rest = pending.split_off(split);
+            let batch = pending.into_iter().map(Ok).collect::<Vec<_>>();
+            let pending = merge_sorted(vec![rest, day], key);
+            Some((batch, (paths, pending, false)))
+        }
+    })
+    .flat_map(stream::iter)
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::{transaction_data_dataframe, TransactionDataCSVRecord};
+    use crate::fetch::MemoryFetcher;
+    use crate::{
+        get_transaction_data, path_transaction_data, write_dataframe_to_parquet, ParquetOptions,
+    };
+    use std::io::Write;
+
+    #[test]
+    fn test_async_month_list() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let html = r#"<ul class="root-months"><li><a href="2023-09/">2023-09</a></li></ul>"#;
+        let (url, request) = crate::test_utils::serve_once(html);
+
+        // mirrors serve the month list next to the month directories
+        let config = AsyncConfig::new(Config::default().with_base_url(format!("{}/mirror", url)));
+        let months = runtime.block_on(config.get_month_list()).unwrap();
+        assert_eq!(months, vec!["2023-09"]);
+        assert_eq!(request.join().unwrap(), "/mirror/index.html");
+    }
+
+    #[test]
+    fn test_async_config() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("2023-09-01_sourcelog.csv", Default::default())
+            .unwrap();
+        zip.write_all(b"timestamp_ms,hash,source\n1693526400001,0x01,local\n")
+            .unwrap();
+        let fetcher = MemoryFetcher::new();
+        fetcher.insert(
+            "2023-09/2023-09-01_sourcelog.csv.zip",
+            zip.finish().unwrap().into_inner(),
+        );
+
+        let data_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(data_dir.path().join("sourcelog")).unwrap();
+        std::fs::create_dir(data_dir.path().join("transaction-data")).unwrap();
+        let config = AsyncConfig::new(
+            Config::new(data_dir.path())
+                .with_progress(false)
+                .with_fetcher(fetcher),
+        );
+
+        runtime.block_on(async {
+            assert_eq!(config.get_month_list().await.unwrap(), vec!["2023-09"]);
+            let report = config.download_sourcelog_file("2023-09-01").await.unwrap();
+            assert_eq!(report.unwrap().rows_read, 1);
+            assert!(config
+                .download_sourcelog_file("2023-09-01")
+                .await
+                .unwrap()
+                .is_none());
+        });
+
+        // rows of the first day after the start of the second day
+        let (day1, day2) = (1_693_526_400_000, 1_693_612_800_000);
+        let record = |timestamp_ms: i64| TransactionDataCSVRecord {
+            timestamp_ms,
+            hash: format!("0x{}", timestamp_ms),
+            ..Default::default()
+        };
+        let days = [
+            ("2023-09-01", vec![record(day1 + 1), record(day2 + 5)]),
+            ("2023-09-02", vec![record(day2 + 1), record(day2 + 10)]),
+        ];
+        for (day, records) in days {
+            write_dataframe_to_parquet(
+                transaction_data_dataframe(&records).unwrap(),
+                path_transaction_data(data_dir.path(), day),
+                &ParquetOptions::default(),
+            )
+            .unwrap();
+        }
+
+        let stream = config.transaction_data((day1, day2 + 100)).unwrap();
+        let streamed = runtime.block_on(stream.collect::<Vec<_>>());
+        let streamed = streamed.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            streamed,
+            get_transaction_data(data_dir.path(), (day1, day2 + 100)).unwrap()
+        );
+        assert_eq!(
+            streamed.iter().map(|r| r.timestamp_ms).collect::<Vec<_>>(),
+            vec![day1 + 1, day2 + 1, day2 + 5, day2 + 10]
+        );
+
+        assert!(matches!(
+            config.raw_transactions((day1, day2)).err(),
+            Some(TransactionRangeError::DayFileNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+//! `config.toml` with the settings of [`Config`], loaded from `~/.config/mempool-dumpster/config.toml`
+//! by the cli. Unset values keep the [`Config`] defaults.
+//!
+//! ```toml
+//! data_dir = "/var/lib/mempool-dumpster"
+//! network = "sepolia"
+//! mirrors = ["file:///mnt/mirror/sepolia"]
+//! kinds = ["sourcelog", "transaction-data"]
+//! retries = 3
+//! concurrency = 4
+//!
+//! [parquet]
+//! codec = "zstd"
+//! compression_level = 10
+//! ```
+
+use crate::download::network_base_url;
+use crate::fetch::{fetcher_from_url, MirrorFetcher};
+use crate::{Config, DataKind, LockMode, ParquetOptions};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub data_dir: Option<PathBuf>,
+    pub base_url: Option<String>,
+    /// network of the default upstream, e.g. `mainnet`, ignored if `base_url` is set
+    pub network: Option<String>,
+    /// urls tried in order when fetching from the base url fails
+    pub mirrors: Vec<String>,
+    /// kinds downloaded by `get` if no kind is given
+    pub kinds: Option<Vec<DataKind>>,
+    pub retries: Option<usize>,
+    pub concurrency: Option<usize>,
+    pub progress: Option<bool>,
+    pub overwrite: Option<bool>,
+    pub dedupe: Option<bool>,
+    pub strict: Option<bool>,
+    pub quarantine: Option<bool>,
+    pub raw_cache: Option<bool>,
+    /// `wait` or `skip` files locked by another process
+    pub lock_mode: Option<LockMode>,
+    /// lock the whole data directory while a cli command writes to it
+    pub global_lock: Option<bool>,
+    pub parquet: Option<ParquetOptions>,
+}
+
+impl ConfigFile {
+    /// `$XDG_CONFIG_HOME/mempool-dumpster/config.toml`, or `~/.config/mempool-dumpster/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("mempool-dumpster").join("config.toml"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| eyre::eyre!("failed to read {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| eyre::eyre!("invalid config {}: {}", path.display(), e))
+    }
+
+    /// Loads the file at the default path, or returns the defaults if there is none.
+    pub fn load_default() -> eyre::Result<Self> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn parse(content: &str) -> eyre::Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn base_url(&self) -> String {
+        match (&self.base_url, &self.network) {
+            (Some(base_url), _) => base_url.clone(),
+            (None, Some(network)) => network_base_url(network),
+            (None, None) => network_base_url("mainnet"),
+        }
+    }
+
+    pub fn into_config(self) -> Config {
+        let defaults = Config::default();
+        let base_url = self.base_url();
+        let mut config = Config {
+            data_dir: self.data_dir.unwrap_or(defaults.data_dir),
+            progress: self.progress.unwrap_or(defaults.progress),
+            overwrite: self.overwrite.unwrap_or(defaults.overwrite),
+            parquet: self.parquet.unwrap_or(defaults.parquet),
+            dedupe: self.dedupe.unwrap_or(defaults.dedupe),
+            strict: self.strict.unwrap_or(defaults.strict),
+            quarantine: self.quarantine.unwrap_or(defaults.quarantine),
+            raw_cache: self.raw_cache.unwrap_or(defaults.raw_cache),
+            retries: self.retries.unwrap_or(defaults.retries),
+            concurrency: self.concurrency.unwrap_or(defaults.concurrency).max(1),
+            lock_mode: self.lock_mode.unwrap_or(defaults.lock_mode),
+            ..defaults
+        };
+        if !self.mirrors.is_empty() {
+            let fetchers = std::iter::once(&base_url)
+                .chain(&self.mirrors)
+                .map(|url| fetcher_from_url(url, &config.agent))
+                .collect();
+            config = config.with_fetcher(MirrorFetcher::new(fetchers));
+        }
+        config.with_base_url(base_url)
+    }
+}
+
+impl Config {
+    /// Config with the settings of the `config.toml` file, see [`ConfigFile`].
+    pub fn from_file(path: impl AsRef<Path>) -> eyre::Result<Config> {
+        Ok(ConfigFile::load(path)?.into_config())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParquetCodec;
+
+    #[test]
+    fn test_config_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+data_dir = "/tmp/mempool"
+network = "sepolia"
+kinds = ["sourcelog", "transaction-data"]
+retries = 3
+concurrency = 4
+dedupe = true
+
+[parquet]
+codec = "zstd"
+compression_level = 10
+"#,
+        )
+        .unwrap();
+
+        let file = ConfigFile::load(&path).unwrap();
+        assert_eq!(
+            file.kinds,
+            Some(vec![DataKind::Sourcelog, DataKind::TransactionData])
+        );
+
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("/tmp/mempool"));
+        assert_eq!(
+            config.base_url,
+            "https://mempool-dumpster.flashbots.net/ethereum/sepolia"
+        );
+        assert_eq!(config.retries, 3);
+        assert_eq!(config.concurrency, 4);
+        assert!(config.dedupe);
+        assert!(!config.strict);
+        assert_eq!(config.parquet.codec, ParquetCodec::Zstd);
+        assert_eq!(config.parquet.compression_level, Some(10));
+        // unset parquet options keep their defaults
+        assert!(config.parquet.dictionary);
+
+        assert!(ConfigFile::parse("datadir = \"/tmp\"").is_err());
+        assert!(Config::from_file(dir.path().join("missing.toml")).is_err());
+    }
+
+    #[test]
+    fn test_network_month_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        // mainnet months are listed on the root page of the upstream website
+        std::fs::write(&path, "network = \"mainnet\"\n").unwrap();
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(config.month_index_base_url(), crate::download::UPSTREAM_URL);
+
+        std::fs::write(&path, "network = \"sepolia\"\n").unwrap();
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(
+            config.month_index_base_url(),
+            crate::network_base_url("sepolia")
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Which days of a time range are in the data directory. Range reads with coverage skip missing
+//! day files instead of failing, and [`Config`](crate::Config) reads can download them first.
+
+use serde::Serialize;
+
+use crate::{DataKind, TimeRange};
+
+/// Days with a gap of more than 10 minutes between the start or end of the requested part of the
+/// day and the first or last row are partially covered.
+pub const PARTIAL_GAP_MS: i64 = 10 * 60 * 1000;
+
+const DAY_MS: i64 = 86_400_000;
+
+/// What a range read does with days that have no file in the data directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MissingDays {
+    /// fail with [`TransactionRangeError::DayFileNotFound`](crate::TransactionRangeError)
+    #[default]
+    Fail,
+    /// skip the days, they are reported as missing
+    Skip,
+    /// download the missing days before reading, requires a [`Config`](crate::Config)
+    Download,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DayStatus {
+    Present,
+    /// file exists but has no rows near the start or end of the requested part of the day
+    Partial,
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct DayCoverage {
+    pub day: String,
+    pub status: DayStatus,
+    /// first and last row of the day in the time range
+    pub first_timestamp_ms: Option<i64>,
+    pub last_timestamp_ms: Option<i64>,
+}
+
+/// Coverage of the days of a range read.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub kind: DataKind,
+    pub range: TimeRange,
+    pub days: Vec<DayCoverage>,
+}
+
+impl CoverageReport {
+    pub(crate) fn new(kind: DataKind, range: TimeRange) -> Self {
+        Self {
+            kind,
+            range,
+            days: Vec::new(),
+        }
+    }
+
+    // day with a file, timestamps of the first and last row in the range
+    pub(crate) fn add(&mut self, day: &str, timestamps: Option<(i64, i64)>) {
+        let day_start = chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .map(|date| {
+                date.and_time(chrono::NaiveTime::MIN)
+                    .and_utc()
+                    .timestamp_millis()
+            })
+            .unwrap_or(self.range.start_ms());
+        let start = self.range.start_ms().max(day_start);
+        let end = self.range.end_ms().min(day_start + DAY_MS);
+        let status = match timestamps {
+            Some((first, last))
+                if first - start <= PARTIAL_GAP_MS && end - 1 - last <= PARTIAL_GAP_MS =>
+            {
+                DayStatus::Present
+            }
+            _ => DayStatus::Partial,
+        };
+        self.days.push(DayCoverage {
+            day: day.to_string(),
+            status,
+            first_timestamp_ms: timestamps.map(|(first, _)| first),
+            last_timestamp_ms: timestamps.map(|(_, last)| last),
+        });
+    }
+
+    pub(crate) fn add_missing(&mut self, day: &str) {
+        self.days.push(DayCoverage {
+            day: day.to_string(),
+            status: DayStatus::Missing,
+            first_timestamp_ms: None,
+            last_timestamp_ms: None,
+        });
+    }
+
+    pub fn days_with(&self, status: DayStatus) -> Vec<&str> {
+        self.days
+            .iter()
+            .filter(|d| d.status == status)
+            .map(|d| d.day.as_str())
+            .collect()
+    }
+
+    pub fn present(&self) -> Vec<&str> {
+        self.days_with(DayStatus::Present)
+    }
+
+    pub fn partial(&self) -> Vec<&str> {
+        self.days_with(DayStatus::Partial)
+    }
+
+    pub fn missing(&self) -> Vec<&str> {
+        self.days_with(DayStatus::Missing)
+    }
+
+    /// All days are present and fully covered.
+    pub fn is_complete(&self) -> bool {
+        self.days.iter().all(|d| d.status == DayStatus::Present)
+    }
+}
+
+#[cfg(feature = "download")]
+mod config {
+    use super::{CoverageReport, MissingDays};
+    use crate::data_dir::existing_day_path;
+    use crate::reader::{self, days_in_range};
+    use crate::{Config, DataKind, RawTransaction, TimeRange, TransactionData};
+    use polars::frame::DataFrame;
+
+    impl Config {
+        /// Downloads the day files of the time range that are not in the data directory.
+        pub fn download_missing_days(
+            &self,
+            kind: DataKind,
+            range: impl Into<TimeRange>,
+        ) -> eyre::Result<()> {
+            let files = days_in_range(&range.into())?
+                .into_iter()
+                .filter(|day| existing_day_path(&self.data_dir, kind, day).is_none())
+                .map(|day| (kind, day))
+                .collect::<Vec<_>>();
+            let mut outcome = Ok(());
+            self.download_files(&files, |_, _, _, result| match result {
+                Ok(_) => true,
+                Err(e) => {
+                    if outcome.is_ok() {
+                        outcome = Err(e);
+                    }
+                    false
+                }
+            });
+            outcome
+        }
+
+        // downloads the missing days if requested, returns whether missing days are skipped
+        fn prepare_range(
+            &self,
+            kind: DataKind,
+            range: &TimeRange,
+            missing: MissingDays,
+        ) -> eyre::Result<bool> {
+            if missing == MissingDays::Download {
+                self.download_missing_days(kind, *range)?;
+            }
+            // days skipped because another process writes them are reported as missing
+            Ok(missing != MissingDays::Fail)
+        }
+
+        /// [`get_dataframe`](crate::get_dataframe) of the data directory with the coverage of
+        /// the range, missing days are handled as requested.
+        pub fn get_dataframe(
+            &self,
+            kind: DataKind,
+            range: impl Into<TimeRange>,
+            missing: MissingDays,
+        ) -> eyre::Result<(DataFrame, CoverageReport)> {
+            let range = range.into();
+            let skip = self.prepare_range(kind, &range, missing)?;
+            Ok(reader::read_dataframe(&self.data_dir, kind, &range, skip)?)
+        }
+
+        pub fn get_raw_transactions(
+            &self,
+            range: impl Into<TimeRange>,
+            missing: MissingDays,
+        ) -> eyre::Result<(Vec<RawTransaction>, CoverageReport)> {
+            let kind = DataKind::Transactions;
+            let range = range.into();
+            let skip = self.prepare_range(kind, &range, missing)?;
+            Ok(reader::read_raw_transactions(&self.data_dir, &range, skip)?)
+        }
+
+        pub fn get_transaction_data(
+            &self,
+            range: impl Into<TimeRange>,
+            missing: MissingDays,
+        ) -> eyre::Result<(Vec<TransactionData>, CoverageReport)> {
+            let kind = DataKind::TransactionData;
+            let range = range.into();
+            let skip = self.prepare_range(kind, &range, missing)?;
+            Ok(reader::read_transaction_data(&self.data_dir, &range, skip)?)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "download"))]
+mod tests {
+    use super::*;
+    use crate::fetch::MemoryFetcher;
+    use crate::test_utils::sourcelog_zip;
+    use crate::{get_dataframe_with_coverage, Config, TransactionRangeError};
+    use std::path::Path;
+
+    // 2023-09-01 00:00:00
+    const START: i64 = 1_693_526_400_000;
+    const END: i64 = START + 3 * DAY_MS;
+    const KIND: DataKind = DataKind::Sourcelog;
+
+    // upstream with a fully covered first day, one row at noon of the second day and a third day
+    fn config(data_dir: &Path) -> Config {
+        let fetcher = MemoryFetcher::new();
+        fetcher.insert(
+            "2023-09/2023-09-01_sourcelog.csv.zip",
+            sourcelog_zip("2023-09-01", &[START + 1000, START + DAY_MS - 1000]),
+        );
+        fetcher.insert(
+            "2023-09/2023-09-02_sourcelog.csv.zip",
+            sourcelog_zip("2023-09-02", &[START + DAY_MS + DAY_MS / 2]),
+        );
+        fetcher.insert(
+            "2023-09/2023-09-03_sourcelog.csv.zip",
+            sourcelog_zip("2023-09-03", &[START + 2 * DAY_MS + 1000]),
+        );
+        Config::new(data_dir)
+            .with_progress(false)
+            .with_fetcher(fetcher)
+    }
+
+    #[test]
+    fn test_day_status() {
+        let mut coverage = CoverageReport::new(KIND, (START, END).into());
+        coverage.add(
+            "2023-09-01",
+            Some((START + PARTIAL_GAP_MS, START + DAY_MS - 1)),
+        );
+        coverage.add(
+            "2023-09-02",
+            Some((START + DAY_MS + PARTIAL_GAP_MS + 1, START + 2 * DAY_MS - 1)),
+        );
+        coverage.add("2023-09-03", None);
+        coverage.add_missing("2023-09-04");
+        assert_eq!(coverage.present(), vec!["2023-09-01"]);
+        assert_eq!(coverage.partial(), vec!["2023-09-02", "2023-09-03"]);
+        assert_eq!(coverage.missing(), vec!["2023-09-04"]);
+        assert!(!coverage.is_complete());
+    }
+
+    #[test]
+    fn test_missing_days_fail() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let err = config(data_dir.path())
+            .get_dataframe(KIND, (START, END), MissingDays::Fail)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionRangeError>(),
+            Some(TransactionRangeError::DayFileNotFound(day)) if day == "2023-09-01"
+        ));
+    }
+
+    #[test]
+    fn test_missing_days_skip() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let config = config(data_dir.path());
+        config.download_file(KIND, "2023-09-01").unwrap();
+        config.download_file(KIND, "2023-09-02").unwrap();
+
+        let (df, coverage) = config
+            .get_dataframe(KIND, (START, END), MissingDays::Skip)
+            .unwrap();
+        assert_eq!(df.height(), 3);
+        assert_eq!(coverage.present(), vec!["2023-09-01"]);
+        assert_eq!(coverage.partial(), vec!["2023-09-02"]);
+        assert_eq!(coverage.missing(), vec!["2023-09-03"]);
+        assert_eq!(coverage.days[0].first_timestamp_ms, Some(START + 1000));
+        assert_eq!(
+            get_dataframe_with_coverage(data_dir.path(), KIND, (START, END))
+                .unwrap()
+                .1,
+            coverage
+        );
+    }
+
+    #[test]
+    fn test_coverage_of_part_of_day() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let config = config(data_dir.path());
+        config.download_file(KIND, "2023-09-02").unwrap();
+
+        let noon = START + DAY_MS + DAY_MS / 2;
+        let (_, coverage) = config
+            .get_dataframe(KIND, (noon - 1, noon + 1), MissingDays::Skip)
+            .unwrap();
+        assert_eq!(coverage.present(), vec!["2023-09-02"]);
+    }
+
+    #[test]
+    fn test_missing_days_download() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let config = config(data_dir.path());
+        config.download_file(KIND, "2023-09-01").unwrap();
+
+        let (df, coverage) = config
+            .get_dataframe(KIND, (START, END), MissingDays::Download)
+            .unwrap();
+        assert_eq!(df.height(), 4);
+        assert!(coverage.missing().is_empty());
+        assert_eq!(coverage.partial(), vec!["2023-09-02", "2023-09-03"]);
+        assert!(KIND.path(data_dir.path(), "2023-09-03").exists());
+    }
+}
@@ -0,0 +1,290 @@
+//! Layout of the data directory. The layout version is stored in the `layout-version` file,
+//! directories with an older layout are upgraded with [`DataDir::migrate`].
+
+use crate::lock::{self, LockMode};
+use crate::{DataKind, Manifest};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::info;
+
+/// Name of the layout version file in the data directory.
+pub const LAYOUT_VERSION_FILE: &str = "layout-version";
+
+/// Layout written by this version:
+/// - 1: `transactions/YYYY-MM-DD.parquet`
+/// - 2: `transactions/YYYY-MM-DD_transactions.parquet`, like the other kinds
+pub const LAYOUT_VERSION: u32 = 2;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DataDirError {
+    #[error(
+        "data directory {} has layout version {version}, run `mempool-dumpster migrate` to upgrade it to version {LAYOUT_VERSION}",
+        .path.display()
+    )]
+    NeedsMigration { path: PathBuf, version: u32 },
+    #[error(
+        "data directory {} has layout version {version}, newer than the supported version {LAYOUT_VERSION}",
+        .path.display()
+    )]
+    UnsupportedVersion { path: PathBuf, version: u32 },
+}
+
+/// Data directory with the layout of [`LAYOUT_VERSION`] or an older one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDir {
+    path: PathBuf,
+    version: u32,
+}
+
+/// File moved by a migration, paths are relative to the data directory.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct MovedFile {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub dry_run: bool,
+    pub from_version: u32,
+    pub to_version: u32,
+    /// moved files, or the files that would be moved in a dry run
+    pub moved: Vec<MovedFile>,
+}
+
+impl DataDir {
+    /// Opens the data directory without changing it. Directories without a version file have the
+    /// current layout unless they contain files of an older one.
+    pub fn open(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let version_path = path.join(LAYOUT_VERSION_FILE);
+        let version = if version_path.exists() {
+            let content = fs::read_to_string(&version_path)?;
+            content.trim().parse().map_err(|_| {
+                eyre::eyre!(
+                    "invalid layout version in {}: {}",
+                    version_path.display(),
+                    content.trim()
+                )
+            })?
+        } else if legacy_transactions_files(&path)?.next().is_some() {
+            1
+        } else {
+            LAYOUT_VERSION
+        };
+        if version > LAYOUT_VERSION {
+            return Err(DataDirError::UnsupportedVersion { path, version }.into());
+        }
+        Ok(Self { path, version })
+    }
+
+    /// Creates the data directory with the directories of all kinds and the version file.
+    /// Fails with [`DataDirError::NeedsMigration`] if the directory has an older layout.
+    pub fn init(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let data_dir = Self::open(path)?;
+        if data_dir.needs_migration() {
+            return Err(DataDirError::NeedsMigration {
+                path: data_dir.path,
+                version: data_dir.version,
+            }
+            .into());
+        }
+        for kind in DataKind::ALL {
+            fs::create_dir_all(data_dir.path.join(kind.name()))?;
+        }
+        let version_path = data_dir.path.join(LAYOUT_VERSION_FILE);
+        if !version_path.exists() {
+            fs::write(version_path, format!("{}\n", LAYOUT_VERSION))?;
+        }
+        Ok(data_dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn needs_migration(&self) -> bool {
+        self.version < LAYOUT_VERSION
+    }
+
+    pub fn day_path(&self, kind: DataKind, day: &str) -> PathBuf {
+        kind.path(&self.path, day)
+    }
+
+    /// Moves the files to the current layout, updates the manifest and writes the version file.
+    /// The data directory is locked while files are moved.
+    pub fn migrate(&mut self, dry_run: bool) -> eyre::Result<MigrationReport> {
+        let mut report = MigrationReport {
+            dry_run,
+            from_version: self.version,
+            to_version: LAYOUT_VERSION,
+            moved: Vec::new(),
+        };
+        if !self.needs_migration() {
+            return Ok(report);
+        }
+
+        // 1 -> 2: transactions/YYYY-MM-DD.parquet -> transactions/YYYY-MM-DD_transactions.parquet
+        for (day, from) in legacy_transactions_files(&self.path)? {
+            let to = DataKind::Transactions.path(&self.path, &day);
+            if to.exists() {
+                return Err(eyre::eyre!(
+                    "can't move {}, {} already exists",
+                    from.display(),
+                    to.display()
+                ));
+            }
+            report.moved.push(MovedFile {
+                from: relative(&self.path, &from),
+                to: relative(&self.path, &to),
+            });
+        }
+        report.moved.sort_by(|a, b| a.from.cmp(&b.from));
+        if dry_run {
+            return Ok(report);
+        }
+
+        let _lock = lock::lock_data_dir(&self.path, LockMode::Wait)?;
+        for moved in &report.moved {
+            info!("Moving {} to {}", moved.from.display(), moved.to.display());
+            fs::rename(self.path.join(&moved.from), self.path.join(&moved.to))?;
+        }
+        if !report.moved.is_empty() {
+            Manifest::update(&self.path, |manifest| {
+                for moved in &report.moved {
+                    if let Some(file_report) =
+                        manifest.remove(&self.path, self.path.join(&moved.from))
+                    {
+                        manifest
+                            .files
+                            .insert(moved.to.to_string_lossy().to_string(), file_report);
+                    }
+                }
+            })?;
+        }
+        fs::write(
+            self.path.join(LAYOUT_VERSION_FILE),
+            format!("{}\n", LAYOUT_VERSION),
+        )?;
+        self.version = LAYOUT_VERSION;
+        Ok(report)
+    }
+}
+
+/// Day file of the kind, files of directories that weren't migrated yet are found too.
+#[cfg(feature = "reader")]
+pub(crate) fn existing_day_path(data_dir: &Path, kind: DataKind, day: &str) -> Option<PathBuf> {
+    let path = kind.path(data_dir, day);
+    if path.exists() {
+        return Some(path);
+    }
+    legacy_day_path(data_dir, kind, day).filter(|path| path.exists())
+}
+
+/// Day file of the kind in layout 1, if it had a different name.
+pub(crate) fn legacy_day_path(data_dir: &Path, kind: DataKind, day: &str) -> Option<PathBuf> {
+    match kind {
+        DataKind::Transactions => Some(data_dir.join(format!("transactions/{}.parquet", day))),
+        _ => None,
+    }
+}
+
+// day and path of the transactions files of layout 1
+fn legacy_transactions_files(
+    data_dir: &Path,
+) -> eyre::Result<impl Iterator<Item = (String, PathBuf)>> {
+    let dir = data_dir.join(DataKind::Transactions.name());
+    let entries = if dir.exists() {
+        fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+    Ok(entries.into_iter().filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let day = name.strip_suffix(".parquet")?;
+        chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()?;
+        Some((day.to_string(), entry.path()))
+    }))
+}
+
+fn relative(data_dir: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(data_dir).unwrap_or(path).to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConversionReport;
+
+    #[test]
+    fn test_migrate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+
+        // new directories get the current layout
+        let data_dir = DataDir::init(&path).unwrap();
+        assert_eq!(data_dir.version(), LAYOUT_VERSION);
+        assert!(path.join("transaction-data").is_dir());
+        assert_eq!(
+            fs::read_to_string(path.join(LAYOUT_VERSION_FILE)).unwrap(),
+            "2\n"
+        );
+
+        // directory written before the version file
+        let legacy = dir.path().join("legacy");
+        fs::create_dir_all(legacy.join("transactions")).unwrap();
+        fs::write(legacy.join("transactions/2023-09-01.parquet"), "parquet").unwrap();
+        let mut manifest = Manifest::default();
+        manifest.files.insert(
+            "transactions/2023-09-01.parquet".to_string(),
+            ConversionReport {
+                rows_read: 10,
+                ..Default::default()
+            },
+        );
+        manifest.save(&legacy).unwrap();
+
+        let mut data_dir = DataDir::open(&legacy).unwrap();
+        assert_eq!(data_dir.version(), 1);
+        let err = DataDir::init(&legacy).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DataDirError>(),
+            Some(&DataDirError::NeedsMigration {
+                path: legacy.clone(),
+                version: 1
+            })
+        );
+        let new_path = legacy.join("transactions/2023-09-01_transactions.parquet");
+        #[cfg(feature = "reader")]
+        assert_eq!(
+            existing_day_path(&legacy, DataKind::Transactions, "2023-09-01"),
+            Some(legacy.join("transactions/2023-09-01.parquet"))
+        );
+
+        let report = data_dir.migrate(true).unwrap();
+        assert_eq!(
+            report.moved,
+            vec![MovedFile {
+                from: PathBuf::from("transactions/2023-09-01.parquet"),
+                to: PathBuf::from("transactions/2023-09-01_transactions.parquet"),
+            }]
+        );
+        assert!(!new_path.exists());
+        assert!(data_dir.needs_migration());
+
+        data_dir.migrate(false).unwrap();
+        assert!(new_path.exists());
+        assert!(!data_dir.needs_migration());
+        assert_eq!(DataDir::open(&legacy).unwrap().version(), LAYOUT_VERSION);
+        let manifest = Manifest::load(&legacy).unwrap();
+        assert_eq!(manifest.get(&legacy, &new_path).unwrap().rows_read, 10);
+        assert_eq!(manifest.files.len(), 1);
+        DataDir::init(&legacy).unwrap();
+    }
+}
@@ -0,0 +1,245 @@
+//! Transactions present in more than one transaction-data day file. The hashes of all days of the
+//! range are indexed in memory, about 150 bytes per hash, so a month of mainnet (tens of millions of
+//! transactions) takes a few GB. Days aren't compared pairwise because a transaction can resurface
+//! long after it was first seen.
+
+use crate::reader::days_in_range;
+use crate::{
+    path_transaction_data, write_dataframe_to_parquet, ParquetOptions, TimeRange,
+    TransactionRangeError,
+};
+use polars::prelude::{col, BooleanChunked, LazyFrame, NewChunkedArray};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::path::Path;
+use tracing::info;
+
+// keeps the earliest record for each key, result is sorted by timestamp
+pub(crate) fn dedupe_earliest<R, K: Hash + Eq>(
+    records: &mut Vec<R>,
+    timestamp_ms: impl Fn(&R) -> i64,
+    key: impl Fn(&R) -> K,
+) {
+    records.sort_by_key(|r| timestamp_ms(r));
+    let mut seen = HashSet::new();
+    let before = records.len();
+    records.retain(|r| seen.insert(key(r)));
+    tracing::debug!("Removed {} duplicate records", before - records.len());
+}
+
+/// Transaction hash that is present in more than one transaction-data day file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct CrossDayDuplicate {
+    pub hash: String,
+    /// days where the hash is present, the first one has the earliest timestamp
+    pub days: Vec<String>,
+    pub first_seen_ms: i64,
+}
+
+// hash -> (day index, earliest timestamp) for each day file the hash is present in
+fn hash_sightings(
+    data_dir: &Path,
+    days: &[String],
+) -> Result<HashMap<String, Vec<(usize, i64)>>, TransactionRangeError> {
+    for day in days {
+        if !path_transaction_data(data_dir, day).exists() {
+            return Err(TransactionRangeError::DayFileNotFound(day.to_string()));
+        }
+    }
+
+    let mut sightings: HashMap<String, Vec<(usize, i64)>> = HashMap::new();
+    for (day_index, day) in days.iter().enumerate() {
+        let df = LazyFrame::scan_parquet(path_transaction_data(data_dir, day), Default::default())?
+            .select([col("hash"), col("timestamp")])
+            .collect()?;
+        let hashes = df.column("hash")?.str()?;
+        let timestamps = &**df.column("timestamp")?.datetime()?;
+        for (hash, timestamp_ms) in hashes.into_iter().zip(timestamps) {
+            let (Some(hash), Some(timestamp_ms)) = (hash, timestamp_ms) else {
+                continue;
+            };
+            let days = sightings.entry(hash.to_string()).or_default();
+            match days.last_mut() {
+                Some((last_day, last_timestamp_ms)) if *last_day == day_index => {
+                    *last_timestamp_ms = (*last_timestamp_ms).min(timestamp_ms);
+                }
+                _ => days.push((day_index, timestamp_ms)),
+            }
+        }
+    }
+    Ok(sightings)
+}
+
+/// Reports hashes present in more than one transaction-data day file. Whole day files for the days
+/// of the time range are checked, see the [module docs](self) for the memory use.
+pub fn find_cross_day_duplicates(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+) -> Result<Vec<CrossDayDuplicate>, TransactionRangeError> {
+    let days = days_in_range(&range.into())?;
+    let sightings = hash_sightings(data_dir.as_ref(), &days)?;
+
+    let mut duplicates = sightings
+        .into_iter()
+        .filter(|(_, sightings)| sightings.len() > 1)
+        .map(|(hash, mut sightings)| {
+            sightings.sort_by_key(|(day, timestamp_ms)| (*timestamp_ms, *day));
+            CrossDayDuplicate {
+                hash,
+                first_seen_ms: sightings[0].1,
+                days: sightings
+                    .iter()
+                    .map(|(day, _)| days[*day].clone())
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    duplicates.sort_by(|a, b| (a.first_seen_ms, &a.hash).cmp(&(b.first_seen_ms, &b.hash)));
+    Ok(duplicates)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct CanonicalSummary {
+    pub rows_read: usize,
+    pub rows_written: usize,
+}
+
+/// Writes transaction-data day files for the days of the time range to `out_dir` (same layout as
+/// the data directory) where each hash is kept once, in the day file and row with the earliest
+/// timestamp. Holds the hashes of the whole range in memory like [`find_cross_day_duplicates`].
+pub fn write_canonical_transaction_data(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+    out_dir: impl AsRef<Path>,
+    parquet: &ParquetOptions,
+) -> eyre::Result<CanonicalSummary> {
+    let data_dir = data_dir.as_ref();
+    let days = days_in_range(&range.into())?;
+
+    // canonical day and timestamp for each hash
+    let canonical = hash_sightings(data_dir, &days)?
+        .into_iter()
+        .filter_map(|(hash, sightings)| {
+            let first = sightings.into_iter().min_by_key(|(day, t)| (*t, *day))?;
+            Some((hash, first))
+        })
+        .collect::<HashMap<_, _>>();
+
+    std::fs::create_dir_all(out_dir.as_ref().join("transaction-data"))?;
+
+    let mut summary = CanonicalSummary::default();
+    for (day_index, day) in days.iter().enumerate() {
+        let df = LazyFrame::scan_parquet(path_transaction_data(data_dir, day), Default::default())?
+            .collect()?;
+
+        let mut written = HashSet::new();
+        let hashes = df.column("hash")?.str()?;
+        let timestamps = &**df.column("timestamp")?.datetime()?;
+        let mask = hashes
+            .into_iter()
+            .zip(timestamps)
+            .map(|(hash, timestamp_ms)| match (hash, timestamp_ms) {
+                (Some(hash), Some(timestamp_ms)) => {
+                    canonical.get(hash) == Some(&(day_index, timestamp_ms)) && written.insert(hash)
+                }
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        let df = df.filter(&BooleanChunked::from_slice("mask", &mask))?;
+
+        summary.rows_read += mask.len();
+        summary.rows_written += df.height();
+
+        let out_path = path_transaction_data(out_dir.as_ref(), day);
+        info!(
+            "Writing {} of {} rows to {}",
+            df.height(),
+            mask.len(),
+            out_path.display()
+        );
+        write_dataframe_to_parquet(df, out_path, parquet)?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_transaction_data;
+    use crate::test_utils::{tx, write_transaction_data};
+
+    // 2023-09-01 and 2023-09-02 00:00:00
+    const DAY1: i64 = 1_693_526_400_000;
+    const DAY2: i64 = 1_693_612_800_000;
+
+    // 0x01 twice in the first day, 0x02 at the end of the first and the start of the second day
+    fn write_days(data_dir: &Path) {
+        write_transaction_data(
+            data_dir,
+            "2023-09-01",
+            &[
+                tx(DAY1 + 10, "0x01"),
+                tx(DAY1 + 20, "0x01"),
+                tx(DAY2 - 1, "0x02"),
+            ],
+        );
+        write_transaction_data(
+            data_dir,
+            "2023-09-02",
+            &[tx(DAY2 + 1, "0x02"), tx(DAY2 + 5, "0x03")],
+        );
+    }
+
+    #[test]
+    fn test_dedupe_earliest() {
+        let mut records = vec![(3, "0x01"), (1, "0x02"), (2, "0x01")];
+        dedupe_earliest(&mut records, |r| r.0, |r| r.1);
+        assert_eq!(records, vec![(1, "0x02"), (2, "0x01")]);
+    }
+
+    #[test]
+    fn test_cross_day_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let duplicates = find_cross_day_duplicates(dir.path(), (DAY1, DAY2 + 1000)).unwrap();
+        assert_eq!(
+            duplicates,
+            vec![CrossDayDuplicate {
+                hash: "0x02".to_string(),
+                days: vec!["2023-09-01".to_string(), "2023-09-02".to_string()],
+                first_seen_ms: DAY2 - 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_canonical_transaction_data() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let out = tempfile::tempdir().unwrap();
+        let summary = write_canonical_transaction_data(
+            dir.path(),
+            (DAY1, DAY2 + 1000),
+            out.path(),
+            &ParquetOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            summary,
+            CanonicalSummary {
+                rows_read: 5,
+                rows_written: 3,
+            }
+        );
+        let txs = get_transaction_data(out.path(), (DAY1, DAY2 + 1000)).unwrap();
+        assert_eq!(
+            txs.iter()
+                .map(|t| (t.hash.as_str(), t.timestamp_ms))
+                .collect::<Vec<_>>(),
+            vec![("0x01", DAY1 + 10), ("0x02", DAY2 - 1), ("0x03", DAY2 + 5)]
+        );
+    }
+}
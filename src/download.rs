@@ -0,0 +1,1387 @@
+use crate::fetch::{self, Fetcher};
+use crate::lock::{self, FileLock, LockMode};
+use crate::progress::{
+    self, FileProgress, ProgressEvent, ProgressRead, ProgressReporter, RecordsProgress,
+};
+use crate::{
+    dedupe, get_month, naive_datetime_from_millis, parse_raw_file_name, path_raw, raw_file_name,
+    schema, write_dataframe_to_parquet, ConversionReport, DataDir, DataKind, Manifest,
+    ParquetOptions,
+};
+use polars::error::PolarsError;
+use polars::frame::DataFrame;
+use polars::io::mmap::MmapBytesReader;
+use polars::prelude::{NamedFrom, ParquetReader, SerReader, Series};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+pub struct Config {
+    pub data_dir: PathBuf,
+    pub base_url: String,
+    pub progress: bool,
+    pub overwrite: bool,
+    pub parquet: ParquetOptions,
+    // keep only the earliest sourcelog record per hash and source,
+    // and the earliest transaction-data record per hash
+    pub dedupe: bool,
+    // fail on rows that can't be deserialized instead of dropping them
+    pub strict: bool,
+    // write dropped rows to the quarantine directory
+    pub quarantine: bool,
+    // keep downloaded upstream files in the raw directory and reuse them
+    pub raw_cache: bool,
+    // fetcher for base_url is used if not set
+    pub fetcher: Option<Box<dyn Fetcher>>,
+    // sends the requests of the fetcher for base_url, connections are reused between files
+    pub agent: ureq::Agent,
+    // receives progress events, progress bars are shown if not set and progress is enabled
+    pub reporter: Option<Box<dyn ProgressReporter>>,
+    // attempts after a failed download of an upstream file
+    pub retries: usize,
+    // files downloaded in parallel by download_files
+    pub concurrency: usize,
+    // what to do if another process writes the same file
+    pub lock_mode: LockMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("./data"),
+            base_url: network_base_url("mainnet"),
+            progress: true,
+            overwrite: false,
+            parquet: ParquetOptions::default(),
+            dedupe: false,
+            strict: false,
+            quarantine: false,
+            raw_cache: false,
+            fetcher: None,
+            agent: ureq::Agent::new(),
+            reporter: None,
+            retries: 0,
+            concurrency: 1,
+            lock_mode: LockMode::Wait,
+        }
+    }
+}
+
+impl Config {
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        Self {
+            data_dir: data_dir.as_ref().to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn with_parquet_options(mut self, parquet: ParquetOptions) -> Self {
+        self.parquet = parquet;
+        self
+    }
+
+    pub fn with_dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn with_quarantine(mut self, quarantine: bool) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    pub fn with_raw_cache(mut self, raw_cache: bool) -> Self {
+        self.raw_cache = raw_cache;
+        self
+    }
+
+    /// Fetches upstream files with the fetcher instead of downloading them from `base_url`.
+    pub fn with_fetcher(mut self, fetcher: impl Fetcher + 'static) -> Self {
+        self.fetcher = Some(Box::new(fetcher));
+        self
+    }
+
+    /// Downloads from `base_url` with the agent, e.g. one with a proxy or authentication headers.
+    pub fn with_agent(mut self, agent: ureq::Agent) -> Self {
+        self.agent = agent;
+        self
+    }
+
+    /// Sends progress events to the reporter instead of showing progress bars.
+    pub fn with_reporter(mut self, reporter: impl ProgressReporter + 'static) -> Self {
+        self.reporter = Some(Box::new(reporter));
+        self
+    }
+
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_lock_mode(mut self, lock_mode: LockMode) -> Self {
+        self.lock_mode = lock_mode;
+        self
+    }
+
+    pub(crate) fn reporter(&self) -> Option<&dyn ProgressReporter> {
+        match &self.reporter {
+            Some(reporter) => Some(reporter.as_ref()),
+            None if self.progress => progress::default_reporter(),
+            None => None,
+        }
+    }
+
+    pub(crate) fn fetch(&self, path: &str) -> eyre::Result<Box<dyn Read + Send>> {
+        match &self.fetcher {
+            Some(fetcher) => fetcher.fetch(path),
+            None => fetch::fetcher_from_url(&self.base_url, &self.agent).fetch(path),
+        }
+    }
+
+    fn list(&self, path: &str) -> eyre::Result<Option<Vec<String>>> {
+        match &self.fetcher {
+            Some(fetcher) => fetcher.list(path),
+            None => fetch::fetcher_from_url(&self.base_url, &self.agent).list(path),
+        }
+    }
+
+    /// Lists available months. Months are read from the index page if the fetcher can't list
+    /// directories.
+    pub fn get_month_list(&self) -> eyre::Result<Vec<String>> {
+        let result = match self.list("")? {
+            Some(names) => names
+                .into_iter()
+                .filter(|name| {
+                    chrono::NaiveDate::parse_from_str(&format!("{}-01", name), "%Y-%m-%d").is_ok()
+                })
+                .collect::<Vec<_>>(),
+            None => {
+                let mut html = String::new();
+                match &self.fetcher {
+                    Some(fetcher) => fetcher.fetch("index.html")?,
+                    None => fetch::fetcher_from_url(self.month_index_base_url(), &self.agent)
+                        .fetch("index.html")?,
+                }
+                .read_to_string(&mut html)?;
+                parse_month_list(&html)
+            }
+        };
+        if result.is_empty() {
+            Err(eyre::eyre!("failed to get month list"))
+        } else {
+            Ok(result)
+        }
+    }
+
+    // the upstream website lists the mainnet months on its root page, mirrors and other networks
+    // are expected to serve the list at `<base url>/index.html`
+    pub(crate) fn month_index_base_url(&self) -> &str {
+        let base_url = self.base_url.trim_end_matches('/');
+        if base_url == network_base_url("mainnet") {
+            UPSTREAM_URL
+        } else {
+            base_url
+        }
+    }
+
+    /// Lists available days in a month.
+    pub fn get_day_list(&self, month: &str) -> eyre::Result<Vec<String>> {
+        let result = match self.list(month)? {
+            Some(names) => names
+                .iter()
+                .filter_map(|name| match parse_raw_file_name(name) {
+                    Some((DataKind::TransactionData, day)) => Some(day),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            None => {
+                let mut html = String::new();
+                self.fetch(&format!("{}/index.html", month))?
+                    .read_to_string(&mut html)?;
+                parse_day_list(&html)
+            }
+        };
+        if result.is_empty() {
+            Err(eyre::eyre!("failed to get day list"))
+        } else {
+            Ok(result)
+        }
+    }
+
+    fn csv_options<'a>(&'a self, kind: DataKind, file_path: &'a Path) -> CsvReadOptions<'a> {
+        let quarantine_path = self.quarantine.then(|| {
+            path_quarantine(
+                &self.data_dir,
+                &file_path.file_stem().unwrap_or_default().to_string_lossy(),
+            )
+        });
+        CsvReadOptions {
+            kind,
+            schemas: schema::SCHEMAS,
+            progress: FileProgress::new(self.reporter(), file_path),
+            strict: self.strict,
+            quarantine_path,
+        }
+    }
+
+    fn record_report(&self, file_path: &Path, report: &ConversionReport) -> eyre::Result<()> {
+        FileProgress::new(self.reporter(), file_path).written()?;
+        if report.rows_dropped > 0 {
+            warn!(
+                "Dropped {} of {} rows of {}",
+                report.rows_dropped,
+                report.rows_read,
+                file_path.display()
+            );
+        }
+        // files converted outside of the data directory are not tracked
+        if !file_path.starts_with(&self.data_dir) {
+            return Ok(());
+        }
+        Manifest::record(&self.data_dir, file_path, report)
+    }
+
+    // true if should skip
+    fn check_file(&self, file_path: impl AsRef<Path>) -> eyre::Result<bool> {
+        let file_path = file_path.as_ref();
+        if file_path.exists() {
+            if self.overwrite {
+                info!("File {} already exists, overwriting", file_path.display());
+            } else {
+                info!(
+                    "File {} already exists, skipping download",
+                    file_path.display()
+                );
+                FileProgress::new(self.reporter(), file_path)
+                    .report(|file| ProgressEvent::Skipped { file });
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // locks the file for writing, returns `None` if the file should be skipped because it is
+    // locked in skip mode, or was written by the process holding the lock
+    pub(crate) fn lock_file(&self, file_path: &Path) -> eyre::Result<Option<FileLock>> {
+        let Some(lock) = lock::lock_file(&self.data_dir, file_path, self.lock_mode)? else {
+            info!(
+                "File {} is locked by another process, skipping",
+                file_path.display()
+            );
+            FileProgress::new(self.reporter(), file_path)
+                .report(|file| ProgressEvent::Skipped { file });
+            return Ok(None);
+        };
+        if self.check_file(file_path)? {
+            return Ok(None);
+        }
+        Ok(Some(lock))
+    }
+
+    /// Downloads and converts sourcelog file, returns `None` if the file already exists.
+    pub fn download_sourcelog_file(&self, day: &str) -> eyre::Result<Option<ConversionReport>> {
+        self.download_file(DataKind::Sourcelog, day)
+    }
+
+    /// Downloads transaction file, returns `None` if the file already exists.
+    pub fn download_transaction_file(&self, day: &str) -> eyre::Result<Option<ConversionReport>> {
+        self.download_file(DataKind::Transactions, day)
+    }
+
+    /// Downloads and converts transaction-data file, returns `None` if the file already exists.
+    pub fn download_transaction_data_file(
+        &self,
+        day: &str,
+    ) -> eyre::Result<Option<ConversionReport>> {
+        // https://mempool-dumpster.flashbots.net/ethereum/mainnet/2023-08/2023-08-31.csv.zip
+        self.download_file(DataKind::TransactionData, day)
+    }
+
+    /// Downloads and converts file of the kind, returns `None` if the file already exists.
+    /// Fails with [`crate::DataDirError::NeedsMigration`] if the data directory has an older layout.
+    pub fn download_file(
+        &self,
+        kind: DataKind,
+        day: &str,
+    ) -> eyre::Result<Option<ConversionReport>> {
+        info!("Downloading {} file for {}", kind, day);
+
+        DataDir::init(&self.data_dir)?;
+        let file_path = kind.path(&self.data_dir, day);
+        let Some(_lock) = self.lock_file(&file_path)? else {
+            return Ok(None);
+        };
+
+        let progress = FileProgress::new(self.reporter(), &file_path);
+        let result = self
+            .fetch_raw_file(day, &raw_file_name(kind, day), progress)
+            .and_then(|raw| self.convert(kind, Cursor::new(raw), &file_path));
+        progress.report_result(result).map(Some)
+    }
+
+    /// Downloads the files with `concurrency` threads. `on_result` is called on the calling thread
+    /// as files finish, no more downloads are started once it returns false.
+    pub fn download_files(
+        &self,
+        files: &[(DataKind, String)],
+        mut on_result: impl FnMut(
+            DataKind,
+            &str,
+            Duration,
+            eyre::Result<Option<ConversionReport>>,
+        ) -> bool,
+    ) {
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..self.concurrency.clamp(1, files.len().max(1)) {
+                let sender = sender.clone();
+                let (next, stop) = (&next, &stop);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let Some((kind, day)) = files.get(next.fetch_add(1, Ordering::Relaxed))
+                        else {
+                            break;
+                        };
+                        let start = Instant::now();
+                        let result = self.download_file(*kind, day);
+                        if sender.send((*kind, day, start.elapsed(), result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            for (kind, day, duration, result) in receiver {
+                if !on_result(kind, day, duration, result) {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+    }
+
+    /// Converts local raw file (`2023-09-01_sourcelog.csv.zip`, `2023-09-01.csv.zip` or
+    /// `2023-09-01.parquet`) to the data directory, returns `None` if the file already exists.
+    /// Fails with [`crate::DataDirError::NeedsMigration`] if the data directory has an older layout.
+    pub fn convert_file(
+        &self,
+        raw_path: impl AsRef<Path>,
+    ) -> eyre::Result<Option<ConversionReport>> {
+        let raw_path = raw_path.as_ref();
+        let file_name = raw_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (kind, day) = parse_raw_file_name(&file_name)
+            .ok_or_else(|| eyre::eyre!("unknown raw file name: {}", raw_path.display()))?;
+        info!("Converting {} file for {}", kind, day);
+
+        DataDir::init(&self.data_dir)?;
+        let file_path = kind.path(&self.data_dir, &day);
+        let Some(_lock) = self.lock_file(&file_path)? else {
+            return Ok(None);
+        };
+
+        let progress = FileProgress::new(self.reporter(), &file_path);
+        let result = (|| {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.convert(kind, fs::File::open(raw_path)?, &file_path)
+        })();
+        progress.report_result(result).map(Some)
+    }
+
+    // converts raw upstream file of the kind to parquet file
+    pub(crate) fn convert(
+        &self,
+        kind: DataKind,
+        reader: impl MmapBytesReader,
+        file_path: &Path,
+    ) -> eyre::Result<ConversionReport> {
+        match kind {
+            DataKind::Sourcelog => self.convert_sourcelog_zip(reader, file_path),
+            DataKind::TransactionData => self.convert_transaction_data_zip(reader, file_path),
+            DataKind::Transactions => self.convert_transactions_parquet(reader, file_path),
+        }
+    }
+
+    /// Converts sourcelog `.csv.zip` to parquet file `out`.
+    pub fn convert_sourcelog_zip(
+        &self,
+        reader: impl Read + Seek,
+        out: impl AsRef<Path>,
+    ) -> eyre::Result<ConversionReport> {
+        let file_path = out.as_ref();
+        let (mut records, report) = read_zip_csv_records::<SourcelogCSVRecord>(
+            reader,
+            &self.csv_options(DataKind::Sourcelog, file_path),
+        )?;
+        if self.dedupe {
+            dedupe::dedupe_earliest(
+                &mut records,
+                |r| r.timestamp_ms,
+                |r| (r.hash.clone(), r.source.clone()),
+            );
+        }
+
+        let df = DataFrame::new(vec![
+            Series::new(
+                "timestamp",
+                records
+                    .iter()
+                    .map(|r| naive_datetime_from_millis(r.timestamp_ms))
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "hash",
+                records.iter().map(|r| r.hash.clone()).collect::<Vec<_>>(),
+            ),
+            Series::new(
+                "source",
+                records.iter().map(|r| r.source.clone()).collect::<Vec<_>>(),
+            ),
+        ])?;
+
+        debug!("Writing sourcelog file to {}", file_path.display());
+        write_dataframe_to_parquet(df, file_path, &self.parquet)?;
+        self.record_report(file_path, &report)?;
+
+        Ok(report)
+    }
+
+    /// Converts transaction-data `.csv.zip` to parquet file `out`.
+    pub fn convert_transaction_data_zip(
+        &self,
+        reader: impl Read + Seek,
+        out: impl AsRef<Path>,
+    ) -> eyre::Result<ConversionReport> {
+        let file_path = out.as_ref();
+        let (mut records, report) = read_zip_csv_records::<TransactionDataCSVRecord>(
+            reader,
+            &self.csv_options(DataKind::TransactionData, file_path),
+        )?;
+        if self.dedupe {
+            dedupe::dedupe_earliest(&mut records, |r| r.timestamp_ms, |r| r.hash.clone());
+        }
+
+        let df = transaction_data_dataframe(&records)?;
+        write_dataframe_to_parquet(df, file_path, &self.parquet)?;
+        self.record_report(file_path, &report)?;
+
+        Ok(report)
+    }
+
+    /// Rewrites upstream transactions parquet file to `out`, sorted like the files we convert ourselves.
+    pub fn convert_transactions_parquet(
+        &self,
+        reader: impl MmapBytesReader,
+        out: impl AsRef<Path>,
+    ) -> eyre::Result<ConversionReport> {
+        let file_path = out.as_ref();
+        let df = ParquetReader::new(reader).finish()?;
+        let report = ConversionReport {
+            rows_read: df.height(),
+            ..Default::default()
+        };
+        write_dataframe_to_parquet(df, file_path, &self.parquet)?;
+        self.record_report(file_path, &report)?;
+
+        Ok(report)
+    }
+    // downloads raw upstream file, or reads it from the raw cache if enabled
+    fn fetch_raw_file(
+        &self,
+        day: &str,
+        file_name: &str,
+        progress: FileProgress,
+    ) -> eyre::Result<Vec<u8>> {
+        let raw_path = path_raw(&self.data_dir, file_name);
+        if self.raw_cache && raw_path.exists() {
+            debug!("Reading cached {}", raw_path.display());
+            return Ok(fs::read(raw_path)?);
+        }
+
+        let path = format!("{}/{}", get_month(day), file_name);
+        debug!("Downloading {}", path);
+        progress.report(|file| ProgressEvent::DownloadStarted {
+            file,
+            source: path.clone(),
+        });
+
+        let mut attempt = 0;
+        let buffer = loop {
+            let result = self.fetch(&path).and_then(|reader| {
+                let mut buffer = Vec::new();
+                ProgressRead::new(reader, progress).read_to_end(&mut buffer)?;
+                Ok(buffer)
+            });
+            match result {
+                Ok(buffer) => break buffer,
+                Err(e) if attempt < self.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Failed to download {}, retrying ({}/{}): {}",
+                        path, attempt, self.retries, e
+                    );
+                    std::thread::sleep(retry_delay(attempt));
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        debug!("Downloaded {} bytes", buffer.len());
+
+        if self.raw_cache {
+            if let Some(parent) = raw_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut tmp_path = raw_path.as_os_str().to_owned();
+            tmp_path.push(".tmp");
+            fs::write(&tmp_path, &buffer)?;
+            fs::rename(&tmp_path, &raw_path)?;
+        }
+
+        Ok(buffer)
+    }
+
+    /// Rewrites existing parquet file with the configured parquet options.
+    pub fn recompress_file(&self, file_path: impl AsRef<Path>) -> eyre::Result<ConversionReport> {
+        let file_path = file_path.as_ref();
+        info!("Recompressing {}", file_path.display());
+
+        let _lock = lock::lock_file(&self.data_dir, file_path, LockMode::Wait)?;
+        let df = ParquetReader::new(fs::File::open(file_path)?).finish()?;
+        let report = ConversionReport {
+            rows_read: df.height(),
+            ..Default::default()
+        };
+
+        write_dataframe_to_parquet(df, file_path, &self.parquet)?;
+        FileProgress::new(self.reporter(), file_path).written()?;
+
+        Ok(report)
+    }
+}
+pub fn get_month_list() -> eyre::Result<Vec<String>> {
+    Config::default().get_month_list()
+}
+
+pub fn get_day_list(month: &str) -> eyre::Result<Vec<String>> {
+    Config::default().get_day_list(month)
+}
+
+/// Base url of the upstream files of the network, e.g. `mainnet` or `sepolia`.
+pub fn network_base_url(network: &str) -> String {
+    format!("{}/ethereum/{}", UPSTREAM_URL, network)
+}
+
+// wait before the retry, doubled after every attempt up to 30s
+pub(crate) fn retry_delay(attempt: usize) -> Duration {
+    Duration::from_secs(1 << attempt.saturating_sub(1).min(5)).min(Duration::from_secs(30))
+}
+
+pub(crate) const UPSTREAM_URL: &str = "https://mempool-dumpster.flashbots.net";
+
+// months from the website index page
+pub(crate) fn parse_month_list(html: &str) -> Vec<String> {
+    let fragment = scraper::Html::parse_document(html);
+    let selector = scraper::Selector::parse("ul.root-months li a").unwrap();
+
+    fragment
+        .select(&selector)
+        .map(|e| e.inner_html())
+        .collect::<Vec<_>>()
+}
+
+// days from the month index page
+pub(crate) fn parse_day_list(html: &str) -> Vec<String> {
+    let fragment = scraper::Html::parse_document(html);
+    let selector = scraper::Selector::parse("table.pure-table tbody tr.c1 td.fn a").unwrap();
+
+    fragment
+        .select(&selector)
+        .filter_map(|e| {
+            e.inner_html()
+                .strip_suffix(".csv.zip")
+                .map(|s| s.to_string())
+        })
+        .collect::<Vec<_>>()
+}
+
+fn path_quarantine(data_dir: impl AsRef<Path>, file_stem: &str) -> PathBuf {
+    data_dir
+        .as_ref()
+        .join(format!("quarantine/{}.csv", file_stem))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+struct SourcelogCSVRecord {
+    timestamp_ms: i64,
+    hash: String,
+    source: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub(crate) struct TransactionDataCSVRecord {
+    pub(crate) timestamp_ms: i64,
+    pub(crate) hash: String,
+    pub(crate) chain_id: String,
+    pub(crate) from: String,
+    pub(crate) to: String,
+    pub(crate) value: String,
+    pub(crate) nonce: String,
+    pub(crate) gas: String,
+    pub(crate) gas_price: String,
+    pub(crate) gas_tip_cap: String,
+    pub(crate) gas_fee_cap: String,
+    pub(crate) data_size: i64,
+    pub(crate) data_4bytes: String,
+    // columns of the newer layouts, see schema::SCHEMAS
+    #[serde(default)]
+    pub(crate) sources: Option<String>,
+    #[serde(default)]
+    pub(crate) included_at_block_height: Option<i64>,
+    #[serde(default)]
+    pub(crate) included_block_timestamp: Option<i64>,
+    #[serde(default)]
+    pub(crate) inclusion_delay_ms: Option<i64>,
+    #[serde(default)]
+    pub(crate) tx_type: Option<i64>,
+    #[serde(default)]
+    pub(crate) blob_gas_fee_cap: Option<String>,
+    #[serde(default)]
+    pub(crate) blob_hashes: Option<String>,
+}
+
+pub(crate) fn transaction_data_dataframe(
+    records: &[TransactionDataCSVRecord],
+) -> Result<DataFrame, PolarsError> {
+    DataFrame::new(vec![
+        Series::new(
+            "timestamp",
+            records
+                .iter()
+                .map(|r| naive_datetime_from_millis(r.timestamp_ms))
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "hash",
+            records.iter().map(|r| r.hash.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "chainId",
+            records
+                .iter()
+                .map(|r| r.chain_id.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "from",
+            records
+                .iter()
+                .map(|r| r.from.to_lowercase())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "to",
+            records
+                .iter()
+                .map(|r| r.to.to_lowercase())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "value",
+            records.iter().map(|r| r.value.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "nonce",
+            records.iter().map(|r| r.nonce.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "gas",
+            records.iter().map(|r| r.gas.clone()).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "gasPrice",
+            records
+                .iter()
+                .map(|r| r.gas_price.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "gasTipCap",
+            records
+                .iter()
+                .map(|r| r.gas_tip_cap.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "gasFeeCap",
+            records
+                .iter()
+                .map(|r| r.gas_fee_cap.clone())
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "dataSize",
+            records.iter().map(|r| r.data_size).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "data4Bytes",
+            records
+                .iter()
+                .map(|r| r.data_4bytes.clone())
+                .collect::<Vec<_>>(),
+        ),
+        schema::list_series("sources", records.iter().map(|r| r.sources.as_deref())),
+        Series::new(
+            "includedAtBlockHeight",
+            records
+                .iter()
+                .map(|r| r.included_at_block_height)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "includedBlockTimestamp",
+            records
+                .iter()
+                .map(|r| r.included_block_timestamp)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "inclusionDelayMs",
+            records
+                .iter()
+                .map(|r| r.inclusion_delay_ms)
+                .collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "txType",
+            records.iter().map(|r| r.tx_type).collect::<Vec<_>>(),
+        ),
+        Series::new(
+            "blobGasFeeCap",
+            records
+                .iter()
+                .map(|r| r.blob_gas_fee_cap.clone())
+                .collect::<Vec<_>>(),
+        ),
+        schema::list_series(
+            "blobHashes",
+            records.iter().map(|r| r.blob_hashes.as_deref()),
+        ),
+    ])
+}
+
+struct CsvReadOptions<'a> {
+    kind: DataKind,
+    // known layouts, see schema::SCHEMAS
+    schemas: &'static [schema::CsvSchema],
+    progress: FileProgress<'a>,
+    strict: bool,
+    // rejected rows are written here with the csv header
+    quarantine_path: Option<PathBuf>,
+}
+
+fn read_zip_csv_records<R: DeserializeOwned>(
+    reader: impl Read + Seek,
+    options: &CsvReadOptions,
+) -> eyre::Result<(Vec<R>, ConversionReport)> {
+    let mut zip = zip::ZipArchive::new(reader)?;
+    // we only have one file in the zip
+    let file = zip.by_index(0)?;
+    read_csv_records(file, options)
+}
+
+fn read_csv_records<R: DeserializeOwned>(
+    reader: impl Read,
+    options: &CsvReadOptions,
+) -> eyre::Result<(Vec<R>, ConversionReport)> {
+    // rows with a wrong number of fields are reported like the rows that fail to deserialize
+    let mut csv = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let schema = schema::detect_schema_in(options.schemas, options.kind, csv.headers()?.iter())?;
+    debug!(
+        "Detected {} csv schema version {}",
+        options.kind, schema.version
+    );
+    // renamed columns are deserialized with their current name, rejected rows keep the csv header
+    let csv_headers = csv.byte_headers()?.clone();
+    let headers = csv
+        .headers()?
+        .iter()
+        .map(|c| schema.column_name(c))
+        .collect::<csv::StringRecord>()
+        .into_byte_record();
+
+    let mut progress = RecordsProgress::new(options.progress);
+
+    let mut result = Vec::new();
+    let mut report = ConversionReport {
+        schema_version: Some(schema.version),
+        ..Default::default()
+    };
+    let mut rejected = Vec::new();
+    for record in csv.byte_records() {
+        progress.inc();
+        report.rows_read += 1;
+        let record = record?;
+        let deserialized = if record.len() != headers.len() {
+            Err(format!(
+                "expected {} fields, found {}",
+                headers.len(),
+                record.len()
+            ))
+        } else {
+            record
+                .deserialize::<R>(Some(&headers))
+                .map_err(|e| e.to_string())
+        };
+        match deserialized {
+            Ok(r) => result.push(r),
+            Err(e) => {
+                let line = record.position().map(|p| p.line()).unwrap_or_default();
+                let error = format!("line {}: {}", line, e);
+                if options.strict {
+                    return Err(eyre::eyre!("Failed to deserialize record, {}", error));
+                }
+                debug!("Failed to deserialize record, {}", error);
+                report.add_error(error);
+                rejected.push(record);
+            }
+        }
+    }
+    progress.finish();
+
+    if let (Some(path), false) = (&options.quarantine_path, rejected.is_empty()) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = csv::WriterBuilder::new().flexible(true).from_path(path)?;
+        writer.write_byte_record(&csv_headers)?;
+        for record in &rejected {
+            writer.write_byte_record(record)?;
+        }
+        writer.flush()?;
+        info!(
+            "Wrote {} rejected rows to {}",
+            rejected.len(),
+            path.display()
+        );
+        report.quarantine_file = Some(path.clone());
+    }
+
+    debug!("Read {} records", result.len());
+
+    Ok((result, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        get_dataframe, get_transaction_data, path_source_log, path_transaction_data, TimeRange,
+        TransactionRangeError,
+    };
+    use chrono::NaiveDate;
+    use std::io::Write;
+
+    #[test]
+    fn test_get_month_list() {
+        let month = get_month_list().expect("failed to get month list");
+        assert!(month.iter().find(|m| *m == "2023-08").is_some());
+        assert!(month.iter().find(|m| *m == "2023-09").is_some());
+    }
+
+    #[test]
+    fn test_month_index_base_url() {
+        let config = Config::default();
+        assert_eq!(config.month_index_base_url(), UPSTREAM_URL);
+        let config = config.with_base_url(format!("{}/", network_base_url("mainnet")));
+        assert_eq!(config.month_index_base_url(), UPSTREAM_URL);
+        let config = config.with_base_url("https://mirror.example/mempool");
+        assert_eq!(
+            config.month_index_base_url(),
+            "https://mirror.example/mempool"
+        );
+    }
+
+    #[test]
+    fn test_get_days_list() {
+        let days = get_day_list("2023-08").expect("failed to get day list");
+        assert!(days.iter().find(|m| *m == "2023-08-08").is_some());
+        assert!(days.iter().find(|m| *m == "2023-08-31").is_some());
+    }
+
+    #[ignore]
+    #[test]
+    fn test_download_sourcelog_file() {
+        let env = tracing_subscriber::EnvFilter::builder()
+            .parse("mempool_dumpster=debug")
+            .unwrap();
+        tracing_subscriber::fmt().with_env_filter(env).init();
+
+        Config::new("data")
+            .with_progress(true)
+            .with_base_url("http://localhost:8000")
+            .with_overwrite(true)
+            .download_sourcelog_file("2023-09-07")
+            .unwrap();
+    }
+
+    #[ignore]
+    #[test]
+    fn test_download_transactions_file() {
+        let env = tracing_subscriber::EnvFilter::builder()
+            .parse("mempool_dumpster=debug")
+            .unwrap();
+        tracing_subscriber::fmt().with_env_filter(env).init();
+
+        Config::new("data")
+            .with_progress(true)
+            .with_base_url("http://localhost:8000")
+            .with_overwrite(true)
+            .download_transaction_file("2023-09-09")
+            .unwrap();
+    }
+
+    #[ignore]
+    #[test]
+    fn test_download_transaction_data_file() {
+        let env = tracing_subscriber::EnvFilter::builder()
+            .parse("mempool_dumpster=debug")
+            .unwrap();
+        tracing_subscriber::fmt().with_env_filter(env).init();
+
+        Config::new("data")
+            .with_progress(true)
+            .with_base_url("http://localhost:8000")
+            .with_overwrite(true)
+            .download_transaction_data_file("2023-08-08")
+            .unwrap();
+    }
+
+    // the third line has an invalid timestamp, the fourth a missing column
+    const SOURCELOG_CSV: &str = "timestamp_ms,hash,source\n\
+                                 1693526400000,0x01,local\n\
+                                 not-a-number,0x02,local\n\
+                                 1693526400002,0x03\n\
+                                 1693526400003,0x04,local\n";
+
+    fn csv_options(
+        kind: DataKind,
+        strict: bool,
+        quarantine_path: Option<PathBuf>,
+    ) -> CsvReadOptions<'static> {
+        CsvReadOptions {
+            kind,
+            schemas: schema::SCHEMAS,
+            progress: FileProgress::new(None, Path::new("")),
+            strict,
+            quarantine_path,
+        }
+    }
+
+    #[test]
+    fn test_read_csv_records_skips_invalid_rows() {
+        let options = csv_options(DataKind::Sourcelog, false, None);
+        let (records, report) =
+            read_csv_records::<SourcelogCSVRecord>(SOURCELOG_CSV.as_bytes(), &options).unwrap();
+        assert_eq!(
+            records.iter().map(|r| r.hash.as_str()).collect::<Vec<_>>(),
+            vec!["0x01", "0x04"]
+        );
+        assert_eq!(report.rows_read, 4);
+        assert_eq!(report.rows_dropped, 2);
+        assert_eq!(report.sample_errors.len(), 2);
+        assert!(report.sample_errors[0].starts_with("line 3:"));
+        assert_eq!(
+            report.sample_errors[1],
+            "line 4: expected 3 fields, found 2"
+        );
+        assert_eq!(report.quarantine_file, None);
+    }
+
+    #[test]
+    fn test_read_csv_records_strict() {
+        let options = csv_options(DataKind::Sourcelog, true, None);
+        assert!(
+            read_csv_records::<SourcelogCSVRecord>(SOURCELOG_CSV.as_bytes(), &options).is_err()
+        );
+    }
+
+    #[test]
+    fn test_read_csv_records_quarantine() {
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine_path = path_quarantine(dir.path(), "2023-09-01_sourcelog");
+        let options = csv_options(DataKind::Sourcelog, false, Some(quarantine_path.clone()));
+        let (_, report) =
+            read_csv_records::<SourcelogCSVRecord>(SOURCELOG_CSV.as_bytes(), &options).unwrap();
+        assert_eq!(report.quarantine_file, Some(quarantine_path.clone()));
+        assert_eq!(
+            fs::read_to_string(&quarantine_path).unwrap(),
+            "timestamp_ms,hash,source\nnot-a-number,0x02,local\n1693526400002,0x03\n"
+        );
+
+        // the report is kept in the manifest
+        let file_path = path_source_log(dir.path(), "2023-09-01");
+        Manifest::record(dir.path(), &file_path, &report).unwrap();
+        let manifest = Manifest::load(dir.path()).unwrap();
+        assert_eq!(manifest.get(dir.path(), &file_path), Some(&report));
+        assert!(manifest
+            .files
+            .contains_key("sourcelog/2023-09-01_sourcelog.parquet"));
+    }
+
+    #[test]
+    fn test_read_csv_records_detects_schema() {
+        // newer layout with optional columns
+        let csv = "timestamp_ms,hash,chain_id,from,to,value,nonce,gas,gas_price,gas_tip_cap,\
+                   gas_fee_cap,data_size,data_4bytes,sources,inclusion_delay_ms\n\
+                   1693526400000,0x01,1,0xaa,0xbb,0,0,21000,1,1,1,0,,local,12000\n\
+                   1693526400001,0x02,1,0xaa,0xbb,0,1,21000,1,1,1,0,,,\n";
+        let options = csv_options(DataKind::TransactionData, true, None);
+        let (records, report) =
+            read_csv_records::<TransactionDataCSVRecord>(csv.as_bytes(), &options).unwrap();
+        assert_eq!(report.schema_version, Some(2));
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.sources.as_deref(), r.inclusion_delay_ms))
+                .collect::<Vec<_>>(),
+            vec![(Some("local"), Some(12000)), (None, None)]
+        );
+    }
+
+    #[test]
+    fn test_read_csv_records_unknown_layout() {
+        let csv = "timestamp_ms,hash,source,origin\n1693526400000,0x01,local,eu\n";
+        let options = csv_options(DataKind::Sourcelog, true, None);
+        let err = read_csv_records::<SourcelogCSVRecord>(csv.as_bytes(), &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "unknown sourcelog csv layout, missing columns: [], unknown columns: [\"origin\"]"
+        );
+    }
+
+    #[test]
+    fn test_read_csv_records_renamed_columns() {
+        const SCHEMAS: &[schema::CsvSchema] = &[schema::CsvSchema {
+            kind: DataKind::Sourcelog,
+            version: 2,
+            required: &["timestamp_ms", "hash", "source"],
+            optional: &[],
+            aliases: &[("origin", "source")],
+        }];
+        let csv = "timestamp_ms,hash,origin\n\
+                   1693526400000,0x01,local\n\
+                   invalid,0x02,local\n";
+
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine_path = path_quarantine(dir.path(), "2023-09-01_sourcelog");
+        let options = CsvReadOptions {
+            schemas: SCHEMAS,
+            ..csv_options(DataKind::Sourcelog, false, Some(quarantine_path.clone()))
+        };
+        let (records, report) =
+            read_csv_records::<SourcelogCSVRecord>(csv.as_bytes(), &options).unwrap();
+        assert_eq!(report.schema_version, Some(2));
+        assert_eq!(records[0].source, "local");
+        // rejected rows are quarantined with the upstream header
+        assert_eq!(
+            fs::read_to_string(&quarantine_path).unwrap(),
+            "timestamp_ms,hash,origin\ninvalid,0x02,local\n"
+        );
+    }
+
+    #[test]
+    fn test_optional_columns_are_written() {
+        let df = transaction_data_dataframe(&[]).unwrap();
+        for schema in schema::SCHEMAS
+            .iter()
+            .filter(|s| s.kind == DataKind::TransactionData)
+        {
+            for column in schema.optional {
+                assert!(
+                    df.get_column_index(column.parquet_name).is_some(),
+                    "{} is not written",
+                    column.parquet_name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_parquet_write_leaves_no_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2023-09-01_transaction-data.parquet");
+        let invalid = ParquetOptions::default()
+            .with_codec(crate::ParquetCodec::Zstd)
+            .with_compression_level(Some(100));
+        let df = transaction_data_dataframe(&[]).unwrap();
+        assert!(write_dataframe_to_parquet(df, &path, &invalid).is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_download_into_legacy_dir() {
+        let data_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(data_dir.path().join("transactions")).unwrap();
+        fs::write(
+            data_dir.path().join("transactions/2023-09-01.parquet"),
+            "parquet",
+        )
+        .unwrap();
+        let config = Config::new(data_dir.path())
+            .with_progress(false)
+            .with_fetcher(crate::fetch::MemoryFetcher::new());
+
+        let err = config
+            .download_file(DataKind::Sourcelog, "2023-09-02")
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<crate::DataDirError>(),
+            Some(&crate::DataDirError::NeedsMigration {
+                path: data_dir.path().to_path_buf(),
+                version: 1,
+            })
+        );
+        assert!(!data_dir.path().join("sourcelog").exists());
+    }
+
+    #[test]
+    fn test_convert_file() {
+        let raw_dir = tempfile::tempdir().unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+
+        let csv = "timestamp_ms,hash,source\n\
+                   1693526400002,0x02,local\n\
+                   1693526400001,0x01,local\n";
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("2023-09-01_sourcelog.csv", Default::default())
+            .unwrap();
+        zip.write_all(csv.as_bytes()).unwrap();
+        let raw = zip.finish().unwrap().into_inner();
+        let raw_path = raw_dir.path().join("2023-09-01_sourcelog.csv.zip");
+        fs::write(&raw_path, &raw).unwrap();
+
+        let config = Config::new(data_dir.path()).with_progress(false);
+        let report = config.convert_file(&raw_path).unwrap().unwrap();
+        assert_eq!(report.rows_read, 2);
+        let df = get_dataframe(
+            data_dir.path(),
+            DataKind::Sourcelog,
+            (1693526400000, 1693526400010),
+        )
+        .unwrap();
+        assert_eq!(
+            df.column("hash")
+                .unwrap()
+                .str()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![Some("0x01"), Some("0x02")]
+        );
+        let manifest = Manifest::load(data_dir.path()).unwrap();
+        assert_eq!(
+            manifest.get(
+                data_dir.path(),
+                path_source_log(data_dir.path(), "2023-09-01")
+            ),
+            Some(&report)
+        );
+
+        // existing files are skipped
+        assert_eq!(config.convert_file(&raw_path).unwrap(), None);
+
+        let out = raw_dir.path().join("sourcelog.parquet");
+        config
+            .convert_sourcelog_zip(Cursor::new(raw), &out)
+            .unwrap();
+        assert!(out.exists());
+
+        assert_eq!(
+            parse_raw_file_name("2023-09-01.csv.zip"),
+            Some((DataKind::TransactionData, "2023-09-01".to_string()))
+        );
+        assert_eq!(
+            parse_raw_file_name("2023-09-01.parquet"),
+            Some((DataKind::Transactions, "2023-09-01".to_string()))
+        );
+        assert_eq!(parse_raw_file_name("index.csv.zip"), None);
+    }
+
+    // 2023-09-09 00:00:00
+    fn day_start() -> i64 {
+        NaiveDate::from_ymd_opt(2023, 9, 9)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis()
+    }
+
+    // transactions at 1s, 2s (type 3 with the optional columns) and 5s of 2023-09-09
+    fn write_transaction_data_day(data_dir: &Path) {
+        fs::create_dir(data_dir.join("transaction-data")).unwrap();
+
+        let record = |timestamp_ms: i64, hash: &str| TransactionDataCSVRecord {
+            timestamp_ms,
+            hash: hash.to_string(),
+            chain_id: "1".to_string(),
+            from: "0xAA".to_string(),
+            to: "0xBB".to_string(),
+            value: "1".to_string(),
+            nonce: "2".to_string(),
+            gas: "21000".to_string(),
+            gas_price: "30".to_string(),
+            gas_tip_cap: "1".to_string(),
+            gas_fee_cap: "30".to_string(),
+            data_size: 4,
+            data_4bytes: "0xa9059cbb".to_string(),
+            ..Default::default()
+        };
+        let blob_tx = TransactionDataCSVRecord {
+            sources: Some("local,bloxroute".to_string()),
+            included_at_block_height: Some(18_100_000),
+            inclusion_delay_ms: Some(11_000),
+            tx_type: Some(3),
+            blob_gas_fee_cap: Some("100".to_string()),
+            blob_hashes: Some("0x01aa,0x01bb".to_string()),
+            ..record(day_start() + 2000, "0x02")
+        };
+        let records = vec![
+            blob_tx,
+            record(day_start() + 1000, "0x01"),
+            record(day_start() + 5000, "0x05"),
+        ];
+        let df = transaction_data_dataframe(&records).unwrap();
+        write_dataframe_to_parquet(
+            df,
+            path_transaction_data(data_dir, "2023-09-09"),
+            &ParquetOptions::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_get_transaction_data() {
+        let dir = tempfile::tempdir().unwrap();
+        write_transaction_data_day(dir.path());
+        let day_start = day_start();
+
+        let res = get_transaction_data(dir.path(), (day_start, day_start + 3000)).unwrap();
+        assert_eq!(
+            res.iter().map(|r| r.hash.as_str()).collect::<Vec<_>>(),
+            vec!["0x01", "0x02"]
+        );
+        assert_eq!(res[0].timestamp_ms, day_start + 1000);
+        assert_eq!(res[0].from, "0xaa");
+        assert_eq!(res[0].data_size, 4);
+        assert_eq!(res[0].data_4bytes, "0xa9059cbb");
+    }
+
+    #[test]
+    fn test_get_transaction_data_optional_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        write_transaction_data_day(dir.path());
+        let day_start = day_start();
+
+        let res = get_transaction_data(dir.path(), (day_start, day_start + 3000)).unwrap();
+        assert_eq!(res[0].sources, None);
+        assert_eq!(res[0].tx_type, None);
+        assert_eq!(
+            res[1].sources,
+            Some(vec!["local".to_string(), "bloxroute".to_string()])
+        );
+        assert_eq!(res[1].included_at_block_height, Some(18_100_000));
+        assert_eq!(res[1].included_block_timestamp_ms, None);
+        assert_eq!(res[1].inclusion_delay_ms, Some(11_000));
+        assert_eq!(res[1].tx_type, Some(3));
+        assert_eq!(res[1].blob_gas_fee_cap.as_deref(), Some("100"));
+        assert_eq!(
+            res[1].blob_hashes,
+            Some(vec!["0x01aa".to_string(), "0x01bb".to_string()])
+        );
+
+        let df = get_dataframe(
+            dir.path(),
+            DataKind::TransactionData,
+            (day_start, day_start + 3000),
+        )
+        .unwrap();
+        assert_eq!(df.shape(), (2, 20));
+    }
+
+    #[test]
+    fn test_get_transaction_data_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        write_transaction_data_day(dir.path());
+        let day_start = day_start();
+
+        // rows at the bounds are included as requested
+        let hashes = |range: TimeRange| {
+            get_transaction_data(dir.path(), range)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.hash)
+                .collect::<Vec<_>>()
+        };
+        let range = TimeRange::new(day_start + 1000, day_start + 2000);
+        assert_eq!(hashes(range), vec!["0x01"]);
+        assert_eq!(hashes(range.with_to_inclusive(true)), vec!["0x01", "0x02"]);
+        assert_eq!(
+            hashes(range.with_from_inclusive(false).with_to_inclusive(true)),
+            vec!["0x02"]
+        );
+    }
+
+    #[test]
+    fn test_get_transaction_data_missing_day() {
+        let dir = tempfile::tempdir().unwrap();
+        write_transaction_data_day(dir.path());
+        let day_start = day_start();
+
+        // the end of the day is excluded, a day file is only needed if a millisecond of the
+        // day is in the range
+        assert!(get_transaction_data(dir.path(), (day_start, day_start + 86_400_000)).is_ok());
+        let err =
+            get_transaction_data(dir.path(), (day_start, day_start + 86_400_001)).unwrap_err();
+        assert!(matches!(err, TransactionRangeError::DayFileNotFound(day) if day == "2023-09-10"));
+    }
+}
@@ -0,0 +1,453 @@
+use crate::reader::{day_paths, days_in_range, scan_day_file};
+use crate::{DataKind, TimeRange};
+use polars::error::{to_compute_err, PolarsError};
+use polars::export::arrow::io::avro;
+use polars::export::arrow::io::avro::avro_schema::file::{Block, CompressedBlock};
+use polars::export::arrow::io::avro::avro_schema::schema::Record;
+use polars::frame::DataFrame;
+use polars::io::{csv, ipc, json};
+use polars::prelude::{
+    ChunkCompare, CsvWriter, DataType, Int64Chunked, IpcWriter, LazyFrame, NamedFrom, Schema,
+    SerWriter, Series, SortMultipleOptions,
+};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    /// Arrow IPC (Feather v2)
+    Ipc,
+    Avro,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [
+        ExportFormat::Csv,
+        ExportFormat::Ndjson,
+        ExportFormat::Ipc,
+        ExportFormat::Avro,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Ipc => "ipc",
+            ExportFormat::Avro => "avro",
+        }
+    }
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ExportFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| format!("unknown export format: {}", s))
+    }
+}
+
+/// Exports files of the given kind for the time range to a single file sorted by timestamp.
+/// Day files are read and written one at a time, so the range doesn't have to fit in memory.
+pub fn export_range(
+    data_dir: impl AsRef<Path>,
+    kind: DataKind,
+    range: impl Into<TimeRange>,
+    format: ExportFormat,
+    out: impl AsRef<Path>,
+) -> eyre::Result<()> {
+    let range = range.into();
+    let days = days_in_range(&range)?;
+    // fail before creating the file if a day file is missing
+    let mut scans = day_paths(data_dir.as_ref(), kind, &days)?
+        .into_iter()
+        .map(|path| scan_day_file(path, &range))
+        .collect::<Result<Vec<_>, _>>()?;
+    // files converted from older upstream layouts don't have the newer columns
+    let mut schema = Schema::new();
+    for scan in &mut scans {
+        schema.merge_from_ref(scan.schema()?.as_ref());
+    }
+
+    let out = out.as_ref();
+    tracing::debug!(
+        "Exporting {} days of {} to {}",
+        days.len(),
+        kind,
+        out.display()
+    );
+    // writes next to the file and replaces it when done, a failed export leaves no partial file
+    let mut tmp_path = out.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    match write_scans(scans, &schema, format, File::create(&tmp_path)?) {
+        Ok(()) => Ok(std::fs::rename(&tmp_path, out)?),
+        Err(err) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(err)
+        }
+    }
+}
+
+fn write_scans(
+    scans: Vec<LazyFrame>,
+    schema: &Schema,
+    format: ExportFormat,
+    file: File,
+) -> eyre::Result<()> {
+    let mut file = BufWriter::new(file);
+    let mut writer = BatchedWriter::new(format, &mut file, schema)?;
+    // day files can overlap at the edges, rows of a day are held back until the next day is read
+    let mut pending: Option<DataFrame> = None;
+    for scan in scans {
+        let df = with_schema(scan.collect()?, schema)?;
+        let first = df.column("timestamp")?.datetime()?.get(0);
+        pending = Some(match (pending, first) {
+            (Some(pending), Some(first)) => {
+                let timestamps: &Int64Chunked = pending.column("timestamp")?.datetime()?;
+                let before = timestamps.lt(first);
+                writer.write(&mut pending.filter(&before)?)?;
+                pending.filter(&!&before)?.vstack(&df)?.sort(
+                    ["timestamp"],
+                    SortMultipleOptions::default().with_maintain_order(true),
+                )?
+            }
+            (Some(pending), None) => pending,
+            (None, _) => df,
+        });
+    }
+    if let Some(mut pending) = pending {
+        writer.write(&mut pending)?;
+    }
+    writer.finish()?;
+    file.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+// columns of the schema in its order, missing columns are null
+fn with_schema(df: DataFrame, schema: &Schema) -> Result<DataFrame, PolarsError> {
+    let height = df.height();
+    let columns = schema
+        .iter()
+        .map(|(name, dtype)| match df.column(name) {
+            Ok(column) => column.cast(dtype),
+            Err(_) => Ok(Series::full_null(name, height, dtype)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    DataFrame::new(columns)
+}
+
+/// Writes dataframe in the given format, binary columns (rawTx) are 0x-prefixed hex-encoded
+/// for csv and ndjson, list columns (sources, blobHashes) are comma separated in csv.
+pub fn export_dataframe(
+    df: &mut DataFrame,
+    format: ExportFormat,
+    writer: impl Write,
+) -> Result<(), PolarsError> {
+    let mut writer = BatchedWriter::new(format, writer, &df.schema())?;
+    writer.write(df)?;
+    writer.finish()
+}
+
+// writes dataframes with the same schema one after another to a single file
+enum BatchedWriter<W: Write> {
+    Csv(csv::write::BatchedWriter<W>),
+    Ndjson(json::BatchedWriter<W>),
+    Ipc(ipc::BatchedWriter<W>),
+    Avro(W, Record),
+}
+
+impl<W: Write> BatchedWriter<W> {
+    fn new(format: ExportFormat, writer: W, schema: &Schema) -> Result<Self, PolarsError> {
+        Ok(match format {
+            ExportFormat::Csv => Self::Csv(CsvWriter::new(writer).batched(schema)?),
+            ExportFormat::Ndjson => Self::Ndjson(json::BatchedWriter::new(writer)),
+            ExportFormat::Ipc => Self::Ipc(IpcWriter::new(writer).batched(schema)?),
+            ExportFormat::Avro => {
+                // same as polars AvroWriter, which can only write a single dataframe
+                let record = avro::write::to_record(&schema.to_arrow(false), String::new())?;
+                let mut writer = writer;
+                avro::avro_schema::write::write_metadata(&mut writer, record.clone(), None)
+                    .map_err(to_compute_err)?;
+                Self::Avro(writer, record)
+            }
+        })
+    }
+
+    fn write(&mut self, df: &mut DataFrame) -> Result<(), PolarsError> {
+        // binary columns can't be represented in text formats
+        if matches!(self, Self::Csv(_) | Self::Ndjson(_)) {
+            hex_encode_binary_columns(df)?;
+        }
+        if matches!(self, Self::Csv(_)) {
+            join_list_columns(df)?;
+        }
+        df.align_chunks();
+        match self {
+            Self::Csv(writer) => writer.write_batch(df),
+            Self::Ndjson(writer) => writer.write_batch(df),
+            Self::Ipc(writer) => writer.write_batch(df),
+            Self::Avro(writer, record) => {
+                for chunk in df.iter_chunks(false, true) {
+                    let mut serializers = chunk
+                        .iter()
+                        .zip(record.fields.iter())
+                        .map(|(array, field)| {
+                            avro::write::new_serializer(array.as_ref(), &field.schema)
+                        })
+                        .collect::<Vec<_>>();
+                    let mut block = Block::new(chunk.len(), Vec::new());
+                    avro::write::serialize(&mut serializers, &mut block);
+                    let mut compressed = CompressedBlock::default();
+                    avro::avro_schema::write::compress(&mut block, &mut compressed, None)
+                        .map_err(to_compute_err)?;
+                    avro::avro_schema::write::write_block(writer, &compressed)
+                        .map_err(to_compute_err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), PolarsError> {
+        match self {
+            Self::Csv(mut writer) => writer.finish(),
+            Self::Ipc(mut writer) => writer.finish(),
+            Self::Ndjson(_) | Self::Avro(..) => Ok(()),
+        }
+    }
+}
+
+fn hex_encode_binary_columns(df: &mut DataFrame) -> Result<(), PolarsError> {
+    let binary_columns = df
+        .get_columns()
+        .iter()
+        .filter(|c| c.dtype() == &DataType::Binary)
+        .map(|c| c.name().to_string())
+        .collect::<Vec<_>>();
+    for name in binary_columns {
+        let encoded = df
+            .column(&name)?
+            .binary()?
+            .into_iter()
+            .map(|b| b.map(|b| format!("0x{}", hex::encode(b))))
+            .collect::<Vec<_>>();
+        df.with_column(Series::new(&name, encoded))?;
+    }
+    Ok(())
+}
+
+fn join_list_columns(df: &mut DataFrame) -> Result<(), PolarsError> {
+    let list_columns = df
+        .get_columns()
+        .iter()
+        .filter(|c| matches!(c.dtype(), DataType::List(_)))
+        .map(|c| c.name().to_string())
+        .collect::<Vec<_>>();
+    for name in list_columns {
+        let joined = df
+            .column(&name)?
+            .list()?
+            .into_iter()
+            .map(|list| {
+                list.map(|list| {
+                    let list = list.cast(&DataType::String)?;
+                    let values = list.str()?.into_iter().flatten().collect::<Vec<_>>();
+                    Ok::<_, PolarsError>(values.join(","))
+                })
+                .transpose()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        df.with_column(Series::new(&name, joined))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::io::avro::AvroReader;
+    use polars::prelude::{IpcReader, SerReader};
+    use std::io::Cursor;
+
+    fn raw_transactions() -> DataFrame {
+        DataFrame::new(vec![
+            Series::new("hash", vec!["0x01", "0x02"]),
+            Series::new("rawTx", vec![vec![0x02u8, 0xf8], vec![0xab]]),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_export_binary_columns() {
+        let df = raw_transactions();
+
+        let mut out = Vec::new();
+        export_dataframe(&mut df.clone(), ExportFormat::Ndjson, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"hash\":\"0x01\",\"rawTx\":\"0x02f8\"}\n{\"hash\":\"0x02\",\"rawTx\":\"0xab\"}\n"
+        );
+
+        let mut out = Vec::new();
+        export_dataframe(&mut df.clone(), ExportFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "hash,rawTx\n0x01,0x02f8\n0x02,0xab\n"
+        );
+
+        let mut out = Vec::new();
+        export_dataframe(&mut df.clone(), ExportFormat::Ipc, &mut out).unwrap();
+        assert_eq!(IpcReader::new(Cursor::new(out)).finish().unwrap(), df);
+    }
+
+    #[test]
+    fn test_export_list_columns() {
+        let df = DataFrame::new(vec![
+            Series::new("hash", vec!["0x01", "0x02"]),
+            Series::new(
+                "sources",
+                vec![
+                    Series::new("", ["local", "bloxroute"]),
+                    Series::new("", ["local"]),
+                ],
+            ),
+        ])
+        .unwrap();
+
+        let mut out = Vec::new();
+        export_dataframe(&mut df.clone(), ExportFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "hash,sources\n0x01,\"local,bloxroute\"\n0x02,local\n"
+        );
+
+        let mut out = Vec::new();
+        export_dataframe(&mut df.clone(), ExportFormat::Avro, &mut out).unwrap();
+        assert_eq!(AvroReader::new(Cursor::new(out)).finish().unwrap(), df);
+    }
+
+    #[test]
+    fn test_export_batches() {
+        let df = raw_transactions();
+        for format in ExportFormat::ALL {
+            let mut out = Vec::new();
+            let mut writer = BatchedWriter::new(format, &mut out, &df.schema()).unwrap();
+            writer.write(&mut df.slice(0, 1)).unwrap();
+            writer.write(&mut df.slice(1, 1)).unwrap();
+            writer.finish().unwrap();
+
+            let mut expected = Vec::new();
+            export_dataframe(&mut df.clone(), format, &mut expected).unwrap();
+            match format {
+                ExportFormat::Csv | ExportFormat::Ndjson => assert_eq!(out, expected),
+                ExportFormat::Ipc => {
+                    assert_eq!(IpcReader::new(Cursor::new(out)).finish().unwrap(), df)
+                }
+                ExportFormat::Avro => {
+                    assert_eq!(AvroReader::new(Cursor::new(out)).finish().unwrap(), df)
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn test_export_range() {
+        use crate::test_utils::{transaction_data_frame, tx, write_transaction_data};
+        use crate::{path_transaction_data, write_dataframe_to_parquet, ParquetOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        // 2023-09-01 and 2023-09-02 00:00:00
+        let (day1, day2) = (1_693_526_400_000, 1_693_612_800_000);
+        // older layout without sources, the last row is seen after the first row of the next day
+        let old_layout = transaction_data_frame(&[tx(day1 + 10, "0x01"), tx(day2 + 5, "0x03")])
+            .drop("sources")
+            .unwrap();
+        let path = path_transaction_data(dir.path(), "2023-09-01");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        write_dataframe_to_parquet(old_layout, path, &ParquetOptions::default()).unwrap();
+        write_transaction_data(
+            dir.path(),
+            "2023-09-02",
+            &[
+                tx(day2 + 1, "0x02").with_sources(&["local"]),
+                tx(day2 + 10, "0x04").with_sources(&["local"]),
+            ],
+        );
+
+        let out = dir.path().join("export.ipc");
+        export_range(
+            dir.path(),
+            DataKind::TransactionData,
+            (day1, day2 + 100),
+            ExportFormat::Ipc,
+            &out,
+        )
+        .unwrap();
+        let df = IpcReader::new(File::open(&out).unwrap()).finish().unwrap();
+        assert_eq!(
+            df,
+            crate::get_dataframe(dir.path(), DataKind::TransactionData, (day1, day2 + 100))
+                .unwrap()
+                .select(df.get_column_names())
+                .unwrap()
+        );
+        let hashes = df.column("hash").unwrap().str().unwrap();
+        assert_eq!(
+            hashes.into_no_null_iter().collect::<Vec<_>>(),
+            vec!["0x01", "0x02", "0x03", "0x04"]
+        );
+        assert_eq!(df.column("sources").unwrap().null_count(), 2);
+
+        let missing = export_range(
+            dir.path(),
+            DataKind::TransactionData,
+            (day1, day2 + 86_400_000 + 1),
+            ExportFormat::Csv,
+            dir.path().join("missing.csv"),
+        );
+        assert!(missing.is_err());
+        assert!(!dir.path().join("missing.csv").exists());
+        assert!(!dir.path().join("export.ipc.tmp").exists());
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn test_export_range_error_keeps_file() {
+        use crate::path_transaction_data;
+        use crate::test_utils::{tx, write_transaction_data};
+
+        let dir = tempfile::tempdir().unwrap();
+        let day = 1_693_526_400_000;
+        write_transaction_data(dir.path(), "2023-09-01", &[tx(day + 10, "0x01")]);
+        // corrupt the pages and keep the footer, the schema is read but the rows are not
+        let path = path_transaction_data(dir.path(), "2023-09-01");
+        let mut content = std::fs::read(&path).unwrap();
+        content[4..64].fill(0xff);
+        std::fs::write(&path, content).unwrap();
+
+        let out = dir.path().join("export.csv");
+        std::fs::write(&out, "previous export").unwrap();
+        let result = export_range(
+            dir.path(),
+            DataKind::TransactionData,
+            (day, day + 100),
+            ExportFormat::Csv,
+            &out,
+        );
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "previous export");
+        assert!(!dir.path().join("export.csv.tmp").exists());
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Source of the upstream files. Paths are relative to the network root,
+/// e.g. `2023-09/2023-09-01.csv.zip`.
+pub trait Fetcher: Send + Sync {
+    fn fetch(&self, path: &str) -> eyre::Result<Box<dyn Read + Send>>;
+
+    /// Lists file and directory names under the path (months in the root, files in a month).
+    /// Returns `None` if listing is not supported and the html index pages should be used.
+    fn list(&self, _path: &str) -> eyre::Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+}
+
+/// Creates fetcher for `file://` directories or HTTP(S) urls, requests are sent with the agent.
+pub fn fetcher_from_url(url: &str, agent: &ureq::Agent) -> Box<dyn Fetcher> {
+    match url.strip_prefix("file://") {
+        Some(dir) => Box::new(FileFetcher::new(dir)),
+        None => Box::new(HttpFetcher::new(url).with_agent(agent.clone())),
+    }
+}
+
+pub struct HttpFetcher {
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+impl HttpFetcher {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Uses the agent for requests, e.g. one with middleware adding authentication headers.
+    pub fn with_agent(mut self, agent: ureq::Agent) -> Self {
+        self.agent = agent;
+        self
+    }
+}
+
+impl Fetcher for HttpFetcher {
+    fn fetch(&self, path: &str) -> eyre::Result<Box<dyn Read + Send>> {
+        let url = format!("{}/{}", self.base_url, path);
+        debug!("Fetching {}", url);
+        Ok(Box::new(self.agent.get(&url).call()?.into_reader()))
+    }
+}
+
+/// Directory with the same layout as the upstream website (`2023-09/2023-09-01.csv.zip`).
+pub struct FileFetcher {
+    dir: PathBuf,
+}
+
+impl FileFetcher {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl Fetcher for FileFetcher {
+    fn fetch(&self, path: &str) -> eyre::Result<Box<dyn Read + Send>> {
+        let path = self.dir.join(path);
+        debug!("Fetching {}", path.display());
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn list(&self, path: &str) -> eyre::Result<Option<Vec<String>>> {
+        let mut names = fs::read_dir(self.dir.join(path))?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+            .collect::<eyre::Result<Vec<_>>>()?;
+        names.sort();
+        Ok(Some(names))
+    }
+}
+
+/// Tries the fetchers in order and returns the first success.
+pub struct MirrorFetcher {
+    fetchers: Vec<Box<dyn Fetcher>>,
+}
+
+impl MirrorFetcher {
+    pub fn new(fetchers: Vec<Box<dyn Fetcher>>) -> Self {
+        Self { fetchers }
+    }
+}
+
+impl Fetcher for MirrorFetcher {
+    fn fetch(&self, path: &str) -> eyre::Result<Box<dyn Read + Send>> {
+        let mut last_error = eyre::eyre!("no mirrors configured");
+        for fetcher in &self.fetchers {
+            match fetcher.fetch(path) {
+                Ok(reader) => return Ok(reader),
+                Err(e) => {
+                    warn!("Failed to fetch {}, trying next mirror: {}", path, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    fn list(&self, path: &str) -> eyre::Result<Option<Vec<String>>> {
+        for fetcher in &self.fetchers {
+            match fetcher.list(path) {
+                Ok(Some(names)) => return Ok(Some(names)),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to list {}, trying next mirror: {}", path, e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Files kept in memory, for tests.
+#[derive(Default)]
+pub struct MemoryFetcher {
+    files: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, path: impl Into<String>, content: impl Into<Vec<u8>>) {
+        self.files
+            .write()
+            .expect("poisoned lock")
+            .insert(path.into(), content.into());
+    }
+}
+
+impl Fetcher for MemoryFetcher {
+    fn fetch(&self, path: &str) -> eyre::Result<Box<dyn Read + Send>> {
+        let files = self.files.read().expect("poisoned lock");
+        let content = files
+            .get(path)
+            .ok_or_else(|| eyre::eyre!("file not found: {}", path))?;
+        Ok(Box::new(Cursor::new(content.clone())))
+    }
+
+    fn list(&self, path: &str) -> eyre::Result<Option<Vec<String>>> {
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path.trim_end_matches('/'))
+        };
+        let files = self.files.read().expect("poisoned lock");
+        let mut names = files
+            .keys()
+            .filter_map(|p| p.strip_prefix(&prefix))
+            .map(|p| p.split('/').next().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        Ok(Some(names))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::sourcelog_zip;
+    use crate::{Config, DataKind};
+
+    fn read(fetcher: &dyn Fetcher, path: &str) -> Vec<u8> {
+        let mut content = Vec::new();
+        fetcher
+            .fetch(path)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn test_memory_fetcher() {
+        let memory = MemoryFetcher::new();
+        memory.insert("2023-09/2023-09-01.csv.zip", "day 1");
+        memory.insert("2023-09/2023-09-02.csv.zip", "day 2");
+
+        assert_eq!(read(&memory, "2023-09/2023-09-01.csv.zip"), b"day 1");
+        assert!(memory.fetch("2023-09/2023-09-03.csv.zip").is_err());
+        assert_eq!(memory.list("").unwrap(), Some(vec!["2023-09".to_string()]));
+        assert_eq!(
+            memory.list("2023-09").unwrap(),
+            Some(vec![
+                "2023-09-01.csv.zip".to_string(),
+                "2023-09-02.csv.zip".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_file_fetcher() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("2023-09")).unwrap();
+        fs::write(dir.path().join("2023-09/2023-09-01.csv.zip"), "day 1").unwrap();
+
+        let fetcher = fetcher_from_url(
+            &format!("file://{}", dir.path().display()),
+            &ureq::Agent::new(),
+        );
+        assert_eq!(
+            read(fetcher.as_ref(), "2023-09/2023-09-01.csv.zip"),
+            b"day 1"
+        );
+        assert!(fetcher.fetch("2023-09/2023-09-02.csv.zip").is_err());
+        assert_eq!(
+            fetcher.list("2023-09").unwrap(),
+            Some(vec!["2023-09-01.csv.zip".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_mirror_fetcher() {
+        let memory = MemoryFetcher::new();
+        memory.insert("2023-09/2023-09-01.csv.zip", "day 1");
+
+        // the first mirror fails and can't list
+        let mirror = MirrorFetcher::new(vec![
+            Box::new(FileFetcher::new("/nonexistent")),
+            Box::new(memory),
+        ]);
+        assert_eq!(read(&mirror, "2023-09/2023-09-01.csv.zip"), b"day 1");
+        assert!(mirror.fetch("2023-09/2023-09-02.csv.zip").is_err());
+        assert_eq!(mirror.list("").unwrap(), Some(vec!["2023-09".to_string()]));
+
+        assert!(MirrorFetcher::new(vec![]).fetch("index.html").is_err());
+    }
+
+    #[test]
+    fn test_config_with_fetcher() {
+        let memory = MemoryFetcher::new();
+        let zip = sourcelog_zip("2023-09-01", &[1_693_526_400_001]);
+        memory.insert("2023-09/2023-09-01_sourcelog.csv.zip", zip.clone());
+        memory.insert("2023-09/2023-09-01.csv.zip", Vec::new());
+        memory.insert("2023-09/2023-09-02.csv.zip", Vec::new());
+
+        let data_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(data_dir.path().join("sourcelog")).unwrap();
+        let config = Config::new(data_dir.path())
+            .with_progress(false)
+            .with_raw_cache(true)
+            .with_fetcher(memory);
+
+        assert_eq!(config.get_month_list().unwrap(), vec!["2023-09"]);
+        assert_eq!(
+            config.get_day_list("2023-09").unwrap(),
+            vec!["2023-09-01", "2023-09-02"]
+        );
+
+        let report = config.download_sourcelog_file("2023-09-01").unwrap();
+        assert_eq!(report.unwrap().rows_read, 1);
+        assert!(DataKind::Sourcelog
+            .path(data_dir.path(), "2023-09-01")
+            .exists());
+        // raw cache is a directory the file fetcher can read
+        let cached = FileFetcher::new(data_dir.path());
+        assert_eq!(read(&cached, "raw/2023-09-01_sourcelog.csv.zip"), zip);
+
+        assert!(config.download_transaction_file("2023-09-01").is_err());
+    }
+}
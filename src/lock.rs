@@ -0,0 +1,186 @@
+//! Advisory locks of the data directory. Writers hold an exclusive lock on
+//! `locks/<relative file path>.lock` while the file is written, so concurrent processes don't write
+//! the same file. Lock files stay out of the directories of the data files and aren't removed with
+//! them. Files are written to a
+//! temporary file and renamed when complete, readers never see partially written files.
+
+use fs4::fs_std::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Lock file of the whole data directory.
+pub const DATA_DIR_LOCK_FILE: &str = ".lock";
+
+/// Directory of the lock files of the data files.
+pub const LOCKS_DIR: &str = "locks";
+
+/// What a writer does if the file is locked by another process.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LockMode {
+    /// wait until the lock is released, the file is skipped if it was written in the meantime
+    #[default]
+    Wait,
+    /// skip the file
+    Skip,
+}
+
+impl LockMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LockMode::Wait => "wait",
+            LockMode::Skip => "skip",
+        }
+    }
+}
+
+impl std::fmt::Display for LockMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for LockMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [LockMode::Wait, LockMode::Skip]
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| format!("unknown lock mode: {}", s))
+    }
+}
+
+/// Exclusive advisory lock, released when dropped. Lock files are kept, removing them while
+/// another process waits for the lock would let two processes hold it.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: fs::File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Waits until the lock is free.
+    pub fn acquire(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let file = open_lock_file(path)?;
+        if !FileExt::try_lock_exclusive(&file)? {
+            debug!("Waiting for lock {}", path.display());
+            FileExt::lock_exclusive(&file)?;
+        }
+        Ok(Self {
+            _file: file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Returns `None` if the lock is held by another process.
+    pub fn try_acquire(path: impl AsRef<Path>) -> eyre::Result<Option<Self>> {
+        let path = path.as_ref();
+        let file = open_lock_file(path)?;
+        if !FileExt::try_lock_exclusive(&file)? {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            _file: file,
+            path: path.to_path_buf(),
+        }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn open_lock_file(path: &Path) -> eyre::Result<fs::File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?)
+}
+
+/// Lock file of the data file, `locks/<relative file path>.lock`. Files outside of the data
+/// directory are locked with `<file>.lock`.
+pub fn lock_path(data_dir: impl AsRef<Path>, file_path: impl AsRef<Path>) -> PathBuf {
+    let data_dir = data_dir.as_ref();
+    let file_path = file_path.as_ref();
+    let mut path = match file_path.strip_prefix(data_dir) {
+        Ok(relative) => data_dir.join(LOCKS_DIR).join(relative),
+        Err(_) => file_path.to_path_buf(),
+    }
+    .into_os_string();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Locks the data file with the mode, returns `None` if the mode is skip and the file is locked.
+pub fn lock_file(
+    data_dir: impl AsRef<Path>,
+    file_path: impl AsRef<Path>,
+    mode: LockMode,
+) -> eyre::Result<Option<FileLock>> {
+    lock(&lock_path(data_dir, file_path), mode)
+}
+
+/// Locks the whole data directory, e.g. to keep overlapping cron runs from running at the same time.
+pub fn lock_data_dir(data_dir: impl AsRef<Path>, mode: LockMode) -> eyre::Result<Option<FileLock>> {
+    lock(&data_dir.as_ref().join(DATA_DIR_LOCK_FILE), mode)
+}
+
+fn lock(path: &Path, mode: LockMode) -> eyre::Result<Option<FileLock>> {
+    match mode {
+        LockMode::Wait => FileLock::acquire(path).map(Some),
+        LockMode::Skip => FileLock::try_acquire(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("sourcelog/2023-09-01_sourcelog.parquet");
+
+        let lock = lock_file(dir.path(), &file, LockMode::Skip)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            lock.path(),
+            dir.path()
+                .join("locks/sourcelog/2023-09-01_sourcelog.parquet.lock")
+        );
+        assert!(lock_file(dir.path(), &file, LockMode::Skip)
+            .unwrap()
+            .is_none());
+
+        // released on drop, waiting writers get the lock
+        let waiter = std::thread::spawn({
+            let (data_dir, file) = (dir.path().to_path_buf(), file.clone());
+            move || lock_file(data_dir, file, LockMode::Wait).unwrap().is_some()
+        });
+        drop(lock);
+        assert!(waiter.join().unwrap());
+        assert!(lock_file(dir.path(), &file, LockMode::Skip)
+            .unwrap()
+            .is_some());
+
+        // files outside of the data directory are locked next to the file
+        assert_eq!(
+            lock_path(dir.path().join("data"), &file),
+            dir.path()
+                .join("sourcelog/2023-09-01_sourcelog.parquet.lock")
+        );
+
+        let data_dir_lock = lock_data_dir(dir.path(), LockMode::Skip).unwrap();
+        assert!(data_dir_lock.is_some());
+        assert!(lock_data_dir(dir.path(), LockMode::Skip).unwrap().is_none());
+    }
+}
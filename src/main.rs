@@ -1,17 +1,65 @@
 use clap::Parser;
-use std::fs::create_dir;
-use std::path::PathBuf;
+use mempool_dumpster::{ConfigFile, ConversionReport, DataDir, DataKind, SlotClock, TimeRange};
+use polars::frame::DataFrame;
+use polars::prelude::{CsvWriter, JsonFormat, JsonWriter, ParquetWriter, SerWriter};
+use serde::Serialize;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// some files failed and were skipped with --ignore-errors
+const EXIT_PARTIAL_FAILURE: i32 = 3;
+// the data directory is locked by another process and the command was skipped with --global-lock
+const EXIT_LOCKED: i32 = 4;
 
 #[derive(Parser, Debug)]
 struct Cli {
+    #[clap(
+        long,
+        global = true,
+        env = "MEMPOOL_CONFIG",
+        help = "Config file, ~/.config/mempool-dumpster/config.toml is used if it exists"
+    )]
+    config: Option<PathBuf>,
     #[clap(
         short,
         long,
         env = "MEMPOOL_DATADIR",
-        default_value = "./data",
-        help = "Directory to store data"
+        help = "Directory to store data [default: ./data]"
+    )]
+    datadir: Option<PathBuf>,
+    #[clap(
+        long,
+        global = true,
+        env = "MEMPOOL_BASE_URL",
+        help = "Upstream url, http(s):// or file:// directory with the same layout [default: mainnet]"
+    )]
+    base_url: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        env = "MEMPOOL_NETWORK",
+        help = "Network of the default upstream url (mainnet, sepolia), ignored if --base-url is set"
+    )]
+    network: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        help = "Mirror urls tried in order when fetching from the base url fails"
+    )]
+    mirror: Vec<String>,
+    #[clap(
+        long,
+        global = true,
+        help = "Attempts after a failed download of an upstream file [default: 0]"
+    )]
+    retries: Option<usize>,
+    #[clap(
+        long,
+        global = true,
+        help = "Files downloaded in parallel by get [default: 1]"
     )]
-    datadir: PathBuf,
+    concurrency: Option<usize>,
     #[clap(
         short,
         long,
@@ -26,10 +74,98 @@ struct Cli {
         help = "Skip errors and continue"
     )]
     ignore_errors: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "Parquet compression (uncompressed, snappy, gzip, lz4, zstd, brotli) [default: gzip]"
+    )]
+    compression: Option<mempool_dumpster::ParquetCodec>,
+    #[clap(long, global = true, help = "Parquet compression level")]
+    compression_level: Option<u32>,
+    #[clap(long, global = true, help = "Parquet row group size in rows")]
+    row_group_size: Option<usize>,
+    #[clap(long, global = true, help = "Parquet data page size in bytes")]
+    data_page_size: Option<usize>,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Write distinct count parquet statistics (boolean columns only)"
+    )]
+    full_statistics: bool,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Disable parquet dictionary encoding"
+    )]
+    no_dictionary: bool,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Sort rows with the same timestamp by hash in written files"
+    )]
+    sort_by_hash: bool,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Keep only the earliest record per hash (and source for sourcelog)"
+    )]
+    dedupe: bool,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Fail on csv rows that can't be deserialized instead of dropping them"
+    )]
+    strict: bool,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Write dropped csv rows to the quarantine directory"
+    )]
+    quarantine: bool,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Keep downloaded upstream files in the raw directory and reuse them"
+    )]
+    raw_cache: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "Wait for or skip files written by another process (wait, skip) [default: wait]"
+    )]
+    lock_mode: Option<mempool_dumpster::LockMode>,
+    #[clap(
+        long,
+        global = true,
+        default_value = "false",
+        help = "Lock the whole data directory while get, convert, recompress or gc run"
+    )]
+    global_lock: bool,
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value = "text",
+        help = "Output format of list-months, list-days, get, convert, recompress, gc, migrate and stats"
+    )]
+    output: OutputFormat,
     #[clap(subcommand)]
     subcmd: Commands,
 }
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
 #[derive(Parser, Debug)]
 enum Commands {
     #[clap(name = "list-months", about = "List available months")]
@@ -58,27 +194,589 @@ enum Commands {
         )]
         transactions: bool,
     },
+    #[clap(
+        name = "convert",
+        about = "Convert already downloaded upstream files (.csv.zip, .parquet)"
+    )]
+    Convert {
+        #[clap(help = "Upstream file or directory with upstream files")]
+        path: PathBuf,
+    },
+    #[clap(
+        name = "recompress",
+        about = "Rewrite downloaded files with the current parquet options"
+    )]
+    Recompress {
+        #[clap(
+            long,
+            alias = "kind",
+            value_delimiter = ',',
+            help = "Kinds of data to rewrite (sourcelog, transaction-data, transactions), all by default"
+        )]
+        kinds: Vec<DataKind>,
+    },
+    #[clap(
+        name = "export",
+        about = "Export downloaded data to csv, ndjson, ipc or avro"
+    )]
+    Export {
+        #[clap(
+            long,
+            default_value = "transaction-data",
+            help = "Kind of data (sourcelog, transaction-data, transactions)"
+        )]
+        kind: DataKind,
+        #[clap(flatten)]
+        range: RangeArgs,
+        #[clap(long, help = "Output format (csv, ndjson, ipc, avro)")]
+        format: mempool_dumpster::export::ExportFormat,
+        #[clap(long, help = "Output file")]
+        out: PathBuf,
+    },
+    #[clap(
+        name = "dedupe",
+        about = "Report hashes present in multiple transaction-data day files"
+    )]
+    Dedupe {
+        #[clap(flatten)]
+        range: RangeArgs,
+        #[clap(
+            long,
+            help = "Write deduplicated transaction-data files to this directory"
+        )]
+        out: Option<PathBuf>,
+    },
+    #[clap(
+        name = "gc",
+        about = "Remove old day files, oldest first, and drop them from the manifest"
+    )]
+    Gc {
+        #[clap(long, help = "Keep the files of the last days, today included")]
+        keep_days: Option<u32>,
+        #[clap(
+            long,
+            value_delimiter = ',',
+            help = "Kinds of data to remove (sourcelog, transaction-data, transactions), all by default"
+        )]
+        kinds: Vec<DataKind>,
+        #[clap(
+            long,
+            help = "Remove the oldest files until the data directory fits in this size (500G, 20T)"
+        )]
+        max_size: Option<String>,
+        #[clap(long, help = "Only print the files that would be removed")]
+        dry_run: bool,
+    },
+    #[clap(
+        name = "migrate",
+        about = "Move the files of the data directory to the current layout"
+    )]
+    Migrate {
+        #[clap(long, help = "Only print the files that would be moved")]
+        dry_run: bool,
+    },
+    #[clap(name = "stats", about = "Analyse downloaded data")]
+    Stats {
+        #[clap(subcommand)]
+        subcmd: StatsCommands,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct RangeArgs {
+    #[clap(
+        long,
+        help = "Start of the range (2023-09-01, 2023-09-01T10:00:00Z or unix ms), included"
+    )]
+    from: String,
+    #[clap(
+        long,
+        help = "End of the range (2023-09-02, 2023-09-01T11:00:00Z or unix ms), excluded"
+    )]
+    to: String,
+    #[clap(long, help = "Exclude transactions seen exactly at the start")]
+    from_exclusive: bool,
+    #[clap(long, help = "Include transactions seen exactly at the end")]
+    to_inclusive: bool,
+    #[clap(
+        long,
+        help = "Widen the range to whole 12s slots of the network, from the block of the start to the block after the end"
+    )]
+    align_slots: bool,
+}
+
+impl RangeArgs {
+    fn time_range(&self, slot_clock: &SlotClock) -> eyre::Result<TimeRange> {
+        let range = TimeRange::parse(&self.from, &self.to)?
+            .with_from_inclusive(!self.from_exclusive)
+            .with_to_inclusive(self.to_inclusive);
+        if range.is_empty() {
+            return Err(eyre::eyre!("empty range {}", range));
+        }
+        Ok(if self.align_slots {
+            range.align_to_slots(slot_clock)
+        } else {
+            range
+        })
+    }
+}
+
+#[derive(Parser, Debug)]
+enum StatsCommands {
+    #[clap(
+        name = "replacements",
+        about = "Print transactions replaced by the same sender and nonce as csv"
+    )]
+    Replacements {
+        #[clap(flatten)]
+        range: RangeArgs,
+    },
+    #[clap(
+        name = "nonces",
+        about = "Print nonce gaps and long pending transactions per sender as csv"
+    )]
+    Nonces {
+        #[clap(flatten)]
+        range: RangeArgs,
+        #[clap(
+            long,
+            default_value = "10m",
+            help = "Report transactions pending for this duration before replacement or the end of the range (30s, 10m, 1h)"
+        )]
+        stuck_after: String,
+    },
+    #[clap(
+        name = "fees",
+        about = "Compute gas price, tip cap and fee cap percentiles per time bucket"
+    )]
+    Fees {
+        #[clap(flatten)]
+        range: RangeArgs,
+        #[clap(long, default_value = "1m", help = "Bucket size (30s, 1m, 1h)")]
+        bucket: String,
+        #[clap(
+            long,
+            help = "Output file (.parquet or .csv), csv is printed to stdout if not set"
+        )]
+        out: Option<PathBuf>,
+    },
+    #[clap(
+        name = "selectors",
+        about = "Count transactions per function selector with function names"
+    )]
+    Selectors {
+        #[clap(flatten)]
+        range: RangeArgs,
+        #[clap(
+            long,
+            help = "Signature file (.json or .csv with selector,signature rows)"
+        )]
+        signatures: Option<PathBuf>,
+        #[clap(
+            long,
+            help = "Output file (.parquet or .csv), csv is printed to stdout if not set"
+        )]
+        out: Option<PathBuf>,
+    },
+    #[clap(
+        name = "inclusion",
+        about = "Find inclusion block and delay for transactions using local block data"
+    )]
+    Inclusion {
+        #[clap(flatten)]
+        range: RangeArgs,
+        #[clap(
+            long,
+            required = true,
+            help = "Block data files or directories (JSON-RPC blocks/receipts or parquet)"
+        )]
+        blocks: Vec<PathBuf>,
+        #[clap(
+            long,
+            help = "Output file (.parquet or .csv), csv is printed to stdout if not set"
+        )]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Serialize)]
+struct ReplacementRow<'a> {
+    from: &'a str,
+    nonce: u64,
+    position: usize,
+    timestamp_ms: i64,
+    hash: &'a str,
+    kind: mempool_dumpster::analysis::ReplacementKind,
+    gas_fee_cap: Option<u128>,
+    gas_tip_cap: Option<u128>,
+    fee_cap_bump_pct: Option<f64>,
+    tip_cap_bump_pct: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct NonceRow<'a> {
+    kind: &'static str,
+    from: &'a str,
+    nonce: u64,
+    next_nonce: Option<u64>,
+    missing: Option<u64>,
+    hash: Option<&'a str>,
+    replaced_by: Option<&'a str>,
+    timestamp_ms: i64,
+    pending_ms: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct DuplicateRow<'a> {
+    hash: &'a str,
+    first_seen_ms: i64,
+    days: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    Written,
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Debug)]
+struct FileResult {
+    day: Option<String>,
+    kind: &'static str,
+    file: PathBuf,
+    status: FileStatus,
+    // size of the written file
+    bytes: Option<u64>,
+    rows: Option<usize>,
+    rows_dropped: Option<usize>,
+    duration_ms: u128,
+    error: Option<String>,
+}
+
+// results of the files processed by get, convert and recompress
+struct FileResults {
+    results: Vec<FileResult>,
+    ignore_errors: bool,
+}
+
+impl FileResults {
+    fn new(ignore_errors: bool) -> Self {
+        Self {
+            results: Vec::new(),
+            ignore_errors,
+        }
+    }
+
+    // runs the step for the file, errors are returned unless ignored
+    fn run(
+        &mut self,
+        day: Option<&str>,
+        kind: DataKind,
+        file: &Path,
+        step: impl FnOnce() -> eyre::Result<Option<ConversionReport>>,
+    ) -> eyre::Result<()> {
+        let start = Instant::now();
+        let result = step();
+        self.record(day, kind, file, start.elapsed(), result)
+    }
+
+    fn record(
+        &mut self,
+        day: Option<&str>,
+        kind: DataKind,
+        file: &Path,
+        duration: Duration,
+        result: eyre::Result<Option<ConversionReport>>,
+    ) -> eyre::Result<()> {
+        let mut file_result = FileResult {
+            day: day.map(|d| d.to_string()),
+            kind: kind.name(),
+            file: file.to_path_buf(),
+            status: FileStatus::Written,
+            bytes: None,
+            rows: None,
+            rows_dropped: None,
+            duration_ms: duration.as_millis(),
+            error: None,
+        };
+        match result {
+            Ok(Some(report)) => {
+                file_result.bytes = std::fs::metadata(file).ok().map(|m| m.len());
+                file_result.rows = Some(report.rows_read);
+                file_result.rows_dropped = Some(report.rows_dropped);
+            }
+            Ok(None) => file_result.status = FileStatus::Skipped,
+            Err(e) => {
+                file_result.status = FileStatus::Failed;
+                file_result.error = Some(format!("{:#}", e));
+                self.results.push(file_result);
+                if !self.ignore_errors {
+                    return Err(e);
+                }
+                tracing::error!("Error: {}", e);
+                return Ok(());
+            }
+        }
+        self.results.push(file_result);
+        Ok(())
+    }
+
+    // prints the results, exits with EXIT_PARTIAL_FAILURE if errors were ignored
+    fn finish(self, output: OutputFormat, outcome: eyre::Result<()>) -> eyre::Result<()> {
+        #[derive(Serialize)]
+        struct Output<'a> {
+            results: &'a [FileResult],
+            failed: usize,
+        }
+
+        let failed = self
+            .results
+            .iter()
+            .filter(|r| r.status == FileStatus::Failed)
+            .count();
+        if output == OutputFormat::Json {
+            print_json(&Output {
+                results: &self.results,
+                failed,
+            })?;
+        }
+        outcome?;
+        if failed > 0 {
+            tracing::error!("{} of {} files failed", failed, self.results.len());
+            std::process::exit(EXIT_PARTIAL_FAILURE);
+        }
+        Ok(())
+    }
+}
+
+fn print_json(value: &impl Serialize) -> eyre::Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+// config file merged with the flags and environment variables, which take precedence
+fn settings(cmd: &Cli) -> eyre::Result<ConfigFile> {
+    let mut settings = match &cmd.config {
+        Some(path) => ConfigFile::load(path)?,
+        None => ConfigFile::load_default()?,
+    };
+    if let Some(datadir) = &cmd.datadir {
+        settings.data_dir = Some(datadir.clone());
+    }
+    if let Some(base_url) = &cmd.base_url {
+        settings.base_url = Some(base_url.clone());
+    }
+    if let Some(network) = &cmd.network {
+        // the network flag replaces a base url of the file
+        if cmd.base_url.is_none() {
+            settings.base_url = None;
+        }
+        settings.network = Some(network.clone());
+    }
+    if !cmd.mirror.is_empty() {
+        settings.mirrors = cmd.mirror.clone();
+    }
+    settings.retries = cmd.retries.or(settings.retries);
+    settings.concurrency = cmd.concurrency.or(settings.concurrency);
+    settings.lock_mode = cmd.lock_mode.or(settings.lock_mode);
+    for (flag, value) in [
+        (cmd.overwrite, &mut settings.overwrite),
+        (cmd.dedupe, &mut settings.dedupe),
+        (cmd.strict, &mut settings.strict),
+        (cmd.quarantine, &mut settings.quarantine),
+        (cmd.raw_cache, &mut settings.raw_cache),
+        (cmd.global_lock, &mut settings.global_lock),
+    ] {
+        if flag {
+            *value = Some(true);
+        }
+    }
+
+    let mut parquet = settings.parquet.take().unwrap_or_default();
+    if let Some(codec) = cmd.compression {
+        parquet = parquet.with_codec(codec);
+    }
+    if cmd.compression_level.is_some() {
+        parquet = parquet.with_compression_level(cmd.compression_level);
+    }
+    if cmd.row_group_size.is_some() {
+        parquet = parquet.with_row_group_size(cmd.row_group_size);
+    }
+    if cmd.data_page_size.is_some() {
+        parquet = parquet.with_data_page_size(cmd.data_page_size);
+    }
+    if cmd.no_dictionary {
+        parquet = parquet.with_dictionary(false);
+    }
+    if cmd.sort_by_hash {
+        parquet = parquet.with_sort_by_hash(true);
+    }
+    if cmd.full_statistics {
+        parquet = parquet.with_full_statistics();
+    }
+    settings.parquet = Some(parquet);
+    Ok(settings)
+}
+
+// writes rows of the stats subcommands to stdout as csv, or as a JSON array with --output json
+fn write_rows<T: Serialize>(
+    rows: impl IntoIterator<Item = T>,
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    match output {
+        OutputFormat::Text => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Json => print_json(&rows.into_iter().collect::<Vec<_>>())?,
+    }
+    Ok(())
+}
+
+// writes parquet or csv depending on the file extension, to stdout if there is no path as csv or,
+// with --output json, as a JSON array
+fn write_dataframe(
+    df: &mut DataFrame,
+    path: Option<&Path>,
+    output: OutputFormat,
+) -> eyre::Result<()> {
+    match path {
+        Some(path) if path.extension().is_some_and(|e| e == "parquet") => {
+            ParquetWriter::new(File::create(path)?).finish(df)?;
+        }
+        Some(path) => {
+            CsvWriter::new(File::create(path)?).finish(df)?;
+        }
+        None if output == OutputFormat::Json => {
+            JsonWriter::new(std::io::stdout())
+                .with_json_format(JsonFormat::Json)
+                .finish(df)?;
+            println!();
+        }
+        None => {
+            CsvWriter::new(std::io::stdout()).finish(df)?;
+        }
+    }
+    Ok(())
+}
+
+// parses durations like 500ms, 30s, 10m, 1h, 1d
+fn parse_duration_ms(value: &str) -> eyre::Result<i64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| eyre::eyre!("invalid duration: {}", value))?;
+    let multiplier = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return Err(eyre::eyre!("invalid duration unit: {}", value)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| eyre::eyre!("duration too large: {}", value))
+}
+
+// parses sizes like 1024, 500M, 500G, 2T (powers of 1024)
+fn parse_size(value: &str) -> eyre::Result<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| eyre::eyre!("invalid size: {}", value))?;
+    let shift = match unit.trim_end_matches(['B', 'b']) {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        "T" | "t" => 40,
+        _ => return Err(eyre::eyre!("invalid size unit: {}", value)),
+    };
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| eyre::eyre!("size too large: {}", value))
 }
 
 fn main() -> eyre::Result<()> {
     let env = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
+    // stdout is kept for the command output
     tracing_subscriber::fmt()
         .without_time()
         .with_env_filter(env)
+        .with_writer(std::io::stderr)
         .init();
     let cmd = Cli::parse();
+    let settings = settings(&cmd)?;
+    let default_kinds = settings.kinds.clone();
+    let global_lock = settings.global_lock.unwrap_or(false);
+    let slot_clock = SlotClock::for_network(settings.network.as_deref().unwrap_or("mainnet"))
+        .unwrap_or(SlotClock::MAINNET);
+    let config = settings.into_config();
+    let datadir = config.data_dir.clone();
+    let parquet = config.parquet.clone();
+
+    // held until the command is done
+    let _data_dir_lock = match cmd.subcmd {
+        Commands::Get { .. }
+        | Commands::Convert { .. }
+        | Commands::Recompress { .. }
+        | Commands::Gc { .. }
+            if global_lock =>
+        {
+            let lock = mempool_dumpster::lock::lock_data_dir(&datadir, config.lock_mode)?;
+            if lock.is_none() {
+                tracing::info!(
+                    "Data directory {} is locked by another process, skipping",
+                    datadir.display()
+                );
+                if cmd.output == OutputFormat::Json {
+                    print_json(&serde_json::json!({
+                        "status": "skipped",
+                        "reason": "data directory is locked by another process",
+                    }))?;
+                }
+                std::process::exit(EXIT_LOCKED);
+            }
+            lock
+        }
+        _ => None,
+    };
 
     match cmd.subcmd {
         Commands::ListMonths => {
-            let months = mempool_dumpster::get_month_list()?;
-            for month in months {
-                println!("{}", month);
+            let months = config.get_month_list()?;
+            match cmd.output {
+                OutputFormat::Text => {
+                    for month in months {
+                        println!("{}", month);
+                    }
+                }
+                OutputFormat::Json => print_json(&serde_json::json!({ "months": months }))?,
             }
         }
         Commands::ListDays { month } => {
-            let days = mempool_dumpster::get_day_list(&month)?;
-            for day in days {
-                println!("{}", day);
+            let days = config.get_day_list(&month)?;
+            match cmd.output {
+                OutputFormat::Text => {
+                    for day in days {
+                        println!("{}", day);
+                    }
+                }
+                OutputFormat::Json => {
+                    print_json(&serde_json::json!({ "month": month, "days": days }))?
+                }
             }
         }
         Commands::Get {
@@ -87,37 +785,20 @@ fn main() -> eyre::Result<()> {
             transaction_data,
             transactions,
         } => {
-            // check if datadir exists
-            if !cmd.datadir.exists() {
-                return Err(eyre::eyre!(
-                    "datadir does not exist: {}",
-                    cmd.datadir.display()
-                ));
-            }
+            DataDir::init(&datadir)?;
 
-            let sourcelog_path = cmd.datadir.join("sourcelog");
-            if !sourcelog_path.exists() {
-                create_dir(&sourcelog_path)?;
-            }
-            let transaction_data_path = cmd.datadir.join("transaction-data");
-            if !transaction_data_path.exists() {
-                create_dir(&transaction_data_path)?;
-            }
-            let transactions_path = cmd.datadir.join("transactions");
-            if !transactions_path.exists() {
-                create_dir(&transactions_path)?;
-            }
-
-            let (sourcelog, transaction_data, transactions) =
-                if sourcelog || transaction_data || transactions {
-                    (sourcelog, transaction_data, transactions)
-                } else {
-                    (true, true, false)
-                };
-
-            let config = mempool_dumpster::Config::new(&cmd.datadir)
-                .with_progress(true)
-                .with_overwrite(cmd.overwrite);
+            let kinds = if sourcelog || transaction_data || transactions {
+                [
+                    (DataKind::Sourcelog, sourcelog),
+                    (DataKind::TransactionData, transaction_data),
+                    (DataKind::Transactions, transactions),
+                ]
+                .into_iter()
+                .filter_map(|(kind, enabled)| enabled.then_some(kind))
+                .collect::<Vec<_>>()
+            } else {
+                default_kinds.unwrap_or(vec![DataKind::Sourcelog, DataKind::TransactionData])
+            };
 
             let month = if day_or_month.split('-').count() == 3 {
                 None
@@ -132,44 +813,297 @@ fn main() -> eyre::Result<()> {
             };
 
             let days = if let Some(month) = month {
-                mempool_dumpster::get_day_list(&month)?
+                config.get_day_list(&month)?
             } else {
                 vec![day_or_month]
             };
 
-            for day in days {
-                if sourcelog {
-                    let result = config.download_sourcelog_file(&day);
-                    if let Err(e) = result {
-                        if cmd.ignore_errors {
-                            tracing::error!("Error: {}", e);
-                        } else {
-                            return Err(e);
+            let files = days
+                .iter()
+                .flat_map(|day| kinds.iter().map(|kind| (*kind, day.clone())))
+                .collect::<Vec<_>>();
+            let mut results = FileResults::new(cmd.ignore_errors);
+            let mut outcome = Ok(());
+            config.download_files(&files, |kind, day, duration, result| {
+                let file = kind.path(&datadir, day);
+                match results.record(Some(day), kind, &file, duration, result) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        // keep the first error, downloads already running still finish
+                        if outcome.is_ok() {
+                            outcome = Err(e);
                         }
+                        false
                     }
                 }
-                if transaction_data {
-                    let result = config.download_transaction_data_file(&day);
-                    if let Err(e) = result {
-                        if cmd.ignore_errors {
-                            tracing::error!("Error: {}", e);
-                        } else {
-                            return Err(e);
-                        }
+            });
+            results.finish(cmd.output, outcome)?;
+        }
+        Commands::Convert { path } => {
+            DataDir::init(&datadir)?;
+            let files = if path.is_dir() {
+                let mut files = std::fs::read_dir(&path)?
+                    .map(|entry| entry.map(|e| e.path()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                files.retain(|f| {
+                    let name = f.file_name().unwrap_or_default().to_string_lossy();
+                    mempool_dumpster::parse_raw_file_name(&name).is_some()
+                });
+                files.sort();
+                files
+            } else {
+                vec![path]
+            };
+
+            let mut results = FileResults::new(cmd.ignore_errors);
+            let outcome = (|| {
+                for file in &files {
+                    let name = file.file_name().unwrap_or_default().to_string_lossy();
+                    let (kind, day) = mempool_dumpster::parse_raw_file_name(&name)
+                        .ok_or_else(|| eyre::eyre!("unknown raw file name: {}", file.display()))?;
+                    let out = kind.path(&datadir, &day);
+                    results.run(Some(&day), kind, &out, || config.convert_file(file))?;
+                }
+                Ok(())
+            })();
+            results.finish(cmd.output, outcome)?;
+        }
+        Commands::Recompress { kinds } => {
+            DataDir::init(&datadir)?;
+            let kinds = if kinds.is_empty() {
+                DataKind::ALL.to_vec()
+            } else {
+                kinds
+            };
+            let mut results = FileResults::new(cmd.ignore_errors);
+            let outcome = (|| {
+                for kind in kinds {
+                    let dir = datadir.join(kind.name());
+                    if !dir.exists() {
+                        continue;
+                    }
+                    let mut files = std::fs::read_dir(&dir)?
+                        .map(|e| e.map(|e| e.path()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    files.retain(|f| f.extension().is_some_and(|e| e == "parquet"));
+                    files.sort();
+
+                    for file in files {
+                        results.run(None, kind, &file, || {
+                            config.recompress_file(&file).map(Some)
+                        })?;
                     }
                 }
-                if transactions {
-                    let result = config.download_transaction_file(&day);
-                    if let Err(e) = result {
-                        if cmd.ignore_errors {
-                            tracing::error!("Error: {}", e);
-                        } else {
-                            return Err(e);
-                        }
+                Ok(())
+            })();
+            results.finish(cmd.output, outcome)?;
+        }
+        Commands::Export {
+            kind,
+            range,
+            format,
+            out,
+        } => {
+            mempool_dumpster::export::export_range(
+                &datadir,
+                kind,
+                range.time_range(&slot_clock)?,
+                format,
+                &out,
+            )?;
+        }
+        Commands::Dedupe { range, out } => {
+            let range = range.time_range(&slot_clock)?;
+            let duplicates = mempool_dumpster::dedupe::find_cross_day_duplicates(&datadir, range)?;
+            tracing::info!("Found {} hashes in multiple day files", duplicates.len());
+
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for duplicate in &duplicates {
+                writer.serialize(DuplicateRow {
+                    hash: &duplicate.hash,
+                    first_seen_ms: duplicate.first_seen_ms,
+                    days: duplicate.days.join(" "),
+                })?;
+            }
+            writer.flush()?;
+
+            if let Some(out) = out {
+                let summary = mempool_dumpster::dedupe::write_canonical_transaction_data(
+                    &datadir, range, &out, &parquet,
+                )?;
+                tracing::info!(
+                    "Wrote {} of {} rows to {}",
+                    summary.rows_written,
+                    summary.rows_read,
+                    out.display()
+                );
+            }
+        }
+        Commands::Gc {
+            keep_days,
+            kinds,
+            max_size,
+            dry_run,
+        } => {
+            if keep_days.is_none() && max_size.is_none() {
+                return Err(eyre::eyre!("--keep-days or --max-size is required"));
+            }
+            let policy = mempool_dumpster::RetentionPolicy::default()
+                .with_keep_days(keep_days)
+                .with_kinds(kinds)
+                .with_max_size(max_size.as_deref().map(parse_size).transpose()?)
+                .with_dry_run(dry_run);
+            DataDir::open(&datadir)?;
+            let report = mempool_dumpster::prune(&datadir, &policy)?;
+            match cmd.output {
+                OutputFormat::Text => {
+                    for file in &report.removed {
+                        println!("{}", file.path.display());
+                    }
+                    tracing::info!(
+                        "{} {} files ({} bytes), {} bytes left",
+                        if dry_run { "Would remove" } else { "Removed" },
+                        report.removed.len(),
+                        report.removed_bytes,
+                        report.remaining_bytes
+                    );
+                }
+                OutputFormat::Json => print_json(&report)?,
+            }
+        }
+        Commands::Migrate { dry_run } => {
+            let mut data_dir = DataDir::open(&datadir)?;
+            let report = data_dir.migrate(dry_run)?;
+            match cmd.output {
+                OutputFormat::Text => {
+                    for moved in &report.moved {
+                        println!("{} -> {}", moved.from.display(), moved.to.display());
                     }
+                    tracing::info!(
+                        "{} {} files, layout version {} -> {}",
+                        if dry_run { "Would move" } else { "Moved" },
+                        report.moved.len(),
+                        report.from_version,
+                        report.to_version
+                    );
                 }
+                OutputFormat::Json => print_json(&report)?,
             }
         }
+        Commands::Stats { subcmd } => match subcmd {
+            StatsCommands::Replacements { range } => {
+                let chains = mempool_dumpster::analysis::get_replacement_chains(
+                    &datadir,
+                    range.time_range(&slot_clock)?,
+                )?;
+
+                let rows = chains.iter().flat_map(|chain| {
+                    chain
+                        .entries
+                        .iter()
+                        .enumerate()
+                        .map(|(position, entry)| ReplacementRow {
+                            from: &chain.from,
+                            nonce: chain.nonce,
+                            position,
+                            timestamp_ms: entry.timestamp_ms,
+                            hash: &entry.hash,
+                            kind: entry.kind,
+                            gas_fee_cap: entry.gas_fee_cap,
+                            gas_tip_cap: entry.gas_tip_cap,
+                            fee_cap_bump_pct: entry.fee_cap_bump_pct,
+                            tip_cap_bump_pct: entry.tip_cap_bump_pct,
+                        })
+                });
+                write_rows(rows, cmd.output)?;
+            }
+            StatsCommands::Nonces { range, stuck_after } => {
+                let report = mempool_dumpster::analysis::nonce_gaps(
+                    &datadir,
+                    range.time_range(&slot_clock)?,
+                    parse_duration_ms(&stuck_after)?,
+                )?;
+                tracing::info!(
+                    "Senders: {}, gaps: {}, stuck transactions: {}",
+                    report.senders,
+                    report.gaps.len(),
+                    report.stuck.len()
+                );
+
+                let gaps = report.gaps.iter().map(|gap| NonceRow {
+                    kind: "gap",
+                    from: &gap.from,
+                    nonce: gap.nonce,
+                    next_nonce: Some(gap.next_nonce),
+                    missing: Some(gap.missing),
+                    hash: None,
+                    replaced_by: None,
+                    timestamp_ms: gap.next_nonce_timestamp_ms,
+                    pending_ms: None,
+                });
+                let stuck = report.stuck.iter().map(|stuck| NonceRow {
+                    kind: "stuck",
+                    from: &stuck.from,
+                    nonce: stuck.nonce,
+                    next_nonce: None,
+                    missing: None,
+                    hash: Some(&stuck.hash),
+                    replaced_by: stuck.replaced_by.as_deref(),
+                    timestamp_ms: stuck.timestamp_ms,
+                    pending_ms: Some(stuck.pending_ms),
+                });
+                write_rows(gaps.chain(stuck), cmd.output)?;
+            }
+            StatsCommands::Fees { range, bucket, out } => {
+                let buckets = mempool_dumpster::analysis::fee_stats(
+                    &datadir,
+                    range.time_range(&slot_clock)?,
+                    parse_duration_ms(&bucket)?,
+                )?;
+                let mut df = mempool_dumpster::analysis::fee_buckets_dataframe(&buckets)?;
+                write_dataframe(&mut df, out.as_deref(), cmd.output)?;
+            }
+            StatsCommands::Selectors {
+                range,
+                signatures,
+                out,
+            } => {
+                let signatures = match signatures {
+                    Some(path) => mempool_dumpster::analysis::SignatureDatabase::from_file(path)?,
+                    None => Default::default(),
+                };
+                let stats = mempool_dumpster::analysis::selector_stats(
+                    &datadir,
+                    range.time_range(&slot_clock)?,
+                    &signatures,
+                )?;
+                let mut df = mempool_dumpster::analysis::selector_stats_dataframe(&stats)?;
+                write_dataframe(&mut df, out.as_deref(), cmd.output)?;
+            }
+            StatsCommands::Inclusion { range, blocks, out } => {
+                let mut inclusions = mempool_dumpster::analysis::BlockInclusions::default();
+                for path in blocks {
+                    inclusions.load_path(path)?;
+                }
+                tracing::info!("Loaded {} included transactions", inclusions.len());
+
+                let rows = mempool_dumpster::analysis::inclusion_report(
+                    &datadir,
+                    range.time_range(&slot_clock)?,
+                    &inclusions,
+                )?;
+                let count = |status| rows.iter().filter(|r| r.status == status).count();
+                tracing::info!(
+                    "Transactions: {}, included: {}, never included: {}, unknown: {}",
+                    rows.len(),
+                    count(mempool_dumpster::analysis::InclusionStatus::Included),
+                    count(mempool_dumpster::analysis::InclusionStatus::NeverIncluded),
+                    count(mempool_dumpster::analysis::InclusionStatus::Unknown),
+                );
+                let mut df = mempool_dumpster::analysis::inclusion_dataframe(&rows)?;
+                write_dataframe(&mut df, out.as_deref(), cmd.output)?;
+            }
+        },
     }
 
     Ok(())
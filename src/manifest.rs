@@ -0,0 +1,108 @@
+use crate::lock::{lock_path, FileLock};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest file in the data directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Number of deserialization errors kept in a report.
+pub const MAX_SAMPLE_ERRORS: usize = 10;
+
+/// Result of converting one downloaded file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConversionReport {
+    /// version of the detected csv schema, see [`crate::schema::SCHEMAS`]
+    pub schema_version: Option<u32>,
+    pub rows_read: usize,
+    pub rows_dropped: usize,
+    /// first few errors of the dropped rows
+    pub sample_errors: Vec<String>,
+    /// csv file with the dropped rows if quarantine is enabled
+    pub quarantine_file: Option<PathBuf>,
+}
+
+impl ConversionReport {
+    #[cfg(feature = "download")]
+    pub(crate) fn add_error(&mut self, error: String) {
+        self.rows_dropped += 1;
+        if self.sample_errors.len() < MAX_SAMPLE_ERRORS {
+            self.sample_errors.push(error);
+        }
+    }
+}
+
+/// Conversion reports of the files in the data directory, keyed by path relative to the data directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ConversionReport>,
+}
+
+impl Manifest {
+    /// Loads manifest from the data directory, missing manifest is empty.
+    pub fn load(data_dir: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = data_dir.as_ref().join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn save(&self, data_dir: impl AsRef<Path>) -> eyre::Result<()> {
+        let path = data_dir.as_ref().join(MANIFEST_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn get(
+        &self,
+        data_dir: impl AsRef<Path>,
+        file_path: impl AsRef<Path>,
+    ) -> Option<&ConversionReport> {
+        self.files
+            .get(&manifest_key(data_dir.as_ref(), file_path.as_ref()))
+    }
+
+    pub fn remove(
+        &mut self,
+        data_dir: impl AsRef<Path>,
+        file_path: impl AsRef<Path>,
+    ) -> Option<ConversionReport> {
+        self.files
+            .remove(&manifest_key(data_dir.as_ref(), file_path.as_ref()))
+    }
+
+    /// Stores report of the file and saves the manifest.
+    #[cfg(feature = "download")]
+    pub(crate) fn record(
+        data_dir: &Path,
+        file_path: &Path,
+        report: &ConversionReport,
+    ) -> eyre::Result<()> {
+        Self::update(data_dir, |manifest| {
+            manifest
+                .files
+                .insert(manifest_key(data_dir, file_path), report.clone());
+        })
+    }
+
+    /// Loads, changes and saves the manifest while holding its lock, so concurrent writers
+    /// don't lose each other's changes.
+    pub(crate) fn update(data_dir: &Path, change: impl FnOnce(&mut Self)) -> eyre::Result<()> {
+        let _lock = FileLock::acquire(lock_path(data_dir, data_dir.join(MANIFEST_FILE)))?;
+        let mut manifest = Self::load(data_dir)?;
+        change(&mut manifest);
+        manifest.save(data_dir)
+    }
+}
+
+fn manifest_key(data_dir: &Path, file_path: &Path) -> String {
+    file_path
+        .strip_prefix(data_dir)
+        .unwrap_or(file_path)
+        .to_string_lossy()
+        .to_string()
+}
@@ -0,0 +1,482 @@
+use crate::TimeRange;
+use polars::error::{PolarsError, PolarsResult};
+use polars::export::arrow::datatypes::{ArrowDataType, PhysicalType, PrimitiveType};
+use polars::frame::DataFrame;
+use polars::prelude::SortMultipleOptions;
+use polars_parquet::parquet::statistics::Statistics;
+use polars_parquet::write::{
+    transverse, BrotliLevel, CompressionOptions, Encoding, FileWriter, GzipLevel, KeyValue,
+    RowGroupIterator, StatisticsOptions, Version, WriteOptions, ZstdLevel,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// Key-value metadata with comma separated list of columns the file is sorted by.
+pub const SORTING_COLUMNS_KEY: &str = "mempool_dumpster.sorting_columns";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ParquetCodec {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+    Brotli,
+}
+
+impl ParquetCodec {
+    pub const ALL: [ParquetCodec; 6] = [
+        ParquetCodec::Uncompressed,
+        ParquetCodec::Snappy,
+        ParquetCodec::Gzip,
+        ParquetCodec::Lz4,
+        ParquetCodec::Zstd,
+        ParquetCodec::Brotli,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParquetCodec::Uncompressed => "uncompressed",
+            ParquetCodec::Snappy => "snappy",
+            ParquetCodec::Gzip => "gzip",
+            ParquetCodec::Lz4 => "lz4",
+            ParquetCodec::Zstd => "zstd",
+            ParquetCodec::Brotli => "brotli",
+        }
+    }
+}
+
+impl std::fmt::Display for ParquetCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for ParquetCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ParquetCodec::ALL
+            .into_iter()
+            .find(|codec| codec.name() == s)
+            .ok_or_else(|| format!("unknown parquet codec: {}", s))
+    }
+}
+
+/// Options used to write parquet files.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct ParquetOptions {
+    pub codec: ParquetCodec,
+    /// gzip: 0-10, zstd: 1-22, brotli: 0-11, codec default if not set
+    pub compression_level: Option<u32>,
+    /// rows per row group, 512^2 if not set
+    pub row_group_size: Option<usize>,
+    /// max data page size in bytes, 1024^2 if not set
+    pub data_page_size: Option<usize>,
+    /// min/max and null count statistics, the writer requires the null count on pages with min/max
+    pub min_max_statistics: bool,
+    /// polars-parquet only computes distinct counts of boolean columns, other columns have none
+    pub distinct_count_statistics: bool,
+    /// dictionary encode strings and integers
+    pub dictionary: bool,
+    /// sort rows with the same timestamp by hash
+    pub sort_by_hash: bool,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        Self {
+            codec: ParquetCodec::Gzip,
+            compression_level: None,
+            row_group_size: None,
+            data_page_size: None,
+            min_max_statistics: true,
+            distinct_count_statistics: false,
+            dictionary: true,
+            sort_by_hash: false,
+        }
+    }
+}
+
+impl ParquetOptions {
+    pub fn with_codec(mut self, codec: ParquetCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn with_compression_level(mut self, level: Option<u32>) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    pub fn with_row_group_size(mut self, row_group_size: Option<usize>) -> Self {
+        self.row_group_size = row_group_size;
+        self
+    }
+
+    pub fn with_data_page_size(mut self, data_page_size: Option<usize>) -> Self {
+        self.data_page_size = data_page_size;
+        self
+    }
+
+    pub fn with_min_max_statistics(mut self, min_max_statistics: bool) -> Self {
+        self.min_max_statistics = min_max_statistics;
+        self
+    }
+
+    /// Enables min/max, null count and distinct count statistics, distinct counts are only
+    /// written for boolean columns.
+    pub fn with_full_statistics(mut self) -> Self {
+        self.min_max_statistics = true;
+        self.distinct_count_statistics = true;
+        self
+    }
+
+    pub fn with_dictionary(mut self, dictionary: bool) -> Self {
+        self.dictionary = dictionary;
+        self
+    }
+
+    pub fn with_sort_by_hash(mut self, sort_by_hash: bool) -> Self {
+        self.sort_by_hash = sort_by_hash;
+        self
+    }
+
+    fn compression(&self) -> PolarsResult<CompressionOptions> {
+        let level = self.compression_level;
+        Ok(match self.codec {
+            ParquetCodec::Uncompressed => CompressionOptions::Uncompressed,
+            ParquetCodec::Snappy => CompressionOptions::Snappy,
+            ParquetCodec::Lz4 => CompressionOptions::Lz4Raw,
+            ParquetCodec::Gzip => CompressionOptions::Gzip(
+                level
+                    .map(|l| GzipLevel::try_new(u8::try_from(l).unwrap_or(u8::MAX)))
+                    .transpose()?,
+            ),
+            ParquetCodec::Zstd => CompressionOptions::Zstd(
+                level
+                    .map(|l| ZstdLevel::try_new(i32::try_from(l).unwrap_or(i32::MAX)))
+                    .transpose()?,
+            ),
+            ParquetCodec::Brotli => {
+                CompressionOptions::Brotli(level.map(BrotliLevel::try_new).transpose()?)
+            }
+        })
+    }
+
+    fn write_options(&self) -> PolarsResult<WriteOptions> {
+        Ok(WriteOptions {
+            statistics: StatisticsOptions {
+                min_value: self.min_max_statistics,
+                max_value: self.min_max_statistics,
+                distinct_count: self.distinct_count_statistics,
+                null_count: self.min_max_statistics,
+            },
+            version: Version::V1,
+            compression: self.compression()?,
+            data_pagesize_limit: self.data_page_size,
+        })
+    }
+
+    fn encoding(&self, data_type: &ArrowDataType) -> Encoding {
+        if !self.dictionary {
+            return Encoding::Plain;
+        }
+        // same as polars ParquetWriter
+        match data_type.to_physical_type() {
+            PhysicalType::Dictionary(_)
+            | PhysicalType::LargeBinary
+            | PhysicalType::LargeUtf8
+            | PhysicalType::Utf8View
+            | PhysicalType::BinaryView => Encoding::RleDictionary,
+            PhysicalType::Primitive(
+                PrimitiveType::Float16 | PrimitiveType::Float32 | PrimitiveType::Float64,
+            ) => Encoding::Plain,
+            PhysicalType::Primitive(_) => Encoding::RleDictionary,
+            _ => Encoding::Plain,
+        }
+    }
+}
+
+/// Writes dataframe as parquet with the given options and key-value metadata.
+/// Returns the size of the file.
+pub fn write_parquet(
+    df: &mut DataFrame,
+    writer: impl Write,
+    options: &ParquetOptions,
+    key_value_metadata: Vec<KeyValue>,
+) -> Result<u64, PolarsError> {
+    let write_options = options.write_options()?;
+    let schema = df.schema().to_arrow(true);
+    let encodings = schema
+        .fields
+        .iter()
+        .map(|f| transverse(&f.data_type, |dt| options.encoding(dt)))
+        .collect::<Vec<_>>();
+
+    let row_group_size = options.row_group_size.unwrap_or(512 * 512).max(1);
+    let mut row_groups = Vec::new();
+    let mut offset = 0;
+    while offset < df.height() {
+        let mut row_group = df.slice(offset as i64, row_group_size);
+        row_group.as_single_chunk_par();
+        row_groups.push(row_group);
+        offset += row_group_size;
+    }
+    let batches = row_groups
+        .iter()
+        .flat_map(|rg| rg.iter_chunks(true, false))
+        .map(Ok);
+
+    let row_groups = RowGroupIterator::try_new(batches, &schema, write_options, encodings)?;
+    let mut writer = FileWriter::try_new(writer, schema.clone(), write_options)?;
+    for group in row_groups {
+        writer.write(group?)?;
+    }
+    let metadata = if key_value_metadata.is_empty() {
+        None
+    } else {
+        Some(key_value_metadata)
+    };
+    writer.end(metadata)
+}
+
+/// Sorts dataframe by timestamp (and hash if enabled) and returns metadata with the sort order.
+/// Dataframes without timestamp column are returned as is.
+pub fn sort_for_writing(
+    df: DataFrame,
+    options: &ParquetOptions,
+) -> Result<(DataFrame, Vec<KeyValue>), PolarsError> {
+    if df.get_column_index("timestamp").is_none() {
+        return Ok((df, Vec::new()));
+    }
+    let mut by = vec!["timestamp"];
+    if options.sort_by_hash && df.get_column_index("hash").is_some() {
+        by.push("hash");
+    }
+    let df = df.sort(
+        by.clone(),
+        SortMultipleOptions::default().with_maintain_order(true),
+    )?;
+    let metadata = vec![KeyValue {
+        key: SORTING_COLUMNS_KEY.to_string(),
+        value: Some(by.join(",")),
+    }];
+    Ok((df, metadata))
+}
+
+/// Returns rows `[start, end)` of a file sorted by timestamp that can contain timestamps in the
+/// range using row group statistics.
+/// Returns `None` if the file is not known to be sorted or has no statistics.
+pub fn timestamp_row_range(
+    path: impl AsRef<Path>,
+    range: &TimeRange,
+) -> Result<Option<(usize, usize)>, PolarsError> {
+    let mut file = std::fs::File::open(path)?;
+    let metadata = polars_parquet::read::read_metadata(&mut file)?;
+
+    let sorted = metadata
+        .key_value_metadata
+        .iter()
+        .flatten()
+        .filter(|kv| kv.key == SORTING_COLUMNS_KEY)
+        .any(|kv| {
+            kv.value
+                .as_deref()
+                .is_some_and(|v| v.split(',').next() == Some("timestamp"))
+        });
+    if !sorted {
+        return Ok(None);
+    }
+
+    let (mut start, mut end, mut offset) = (0, 0, 0);
+    for row_group in &metadata.row_groups {
+        let column = row_group
+            .columns()
+            .iter()
+            .find(|c| c.descriptor().path_in_schema == ["timestamp"]);
+        let Some(Ok(Statistics::Int64(stats))) = column.and_then(|c| c.statistics()) else {
+            return Ok(None);
+        };
+        let (Some(min), Some(max)) = (stats.min_value, stats.max_value) else {
+            return Ok(None);
+        };
+
+        offset += row_group.num_rows();
+        if max < range.start_ms() {
+            start = offset;
+        }
+        if min < range.end_ms() {
+            end = offset;
+        }
+    }
+    Ok(Some((start, end.max(start))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::{NamedFrom, ParquetReader, SerReader, Series};
+    use std::io::Cursor;
+
+    fn test_frame() -> DataFrame {
+        DataFrame::new(vec![
+            Series::new(
+                "hash",
+                (0..1000).map(|i| format!("0x{}", i)).collect::<Vec<_>>(),
+            ),
+            Series::new("dataSize", (0..1000i64).collect::<Vec<_>>()),
+            Series::new(
+                "included",
+                (0..1000).map(|i| i % 2 == 0).collect::<Vec<_>>(),
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn write(df: &mut DataFrame, options: &ParquetOptions) -> ParquetReader<Cursor<Vec<u8>>> {
+        let mut buffer = Vec::new();
+        write_parquet(df, &mut buffer, options, vec![]).unwrap();
+        ParquetReader::new(Cursor::new(buffer))
+    }
+
+    #[test]
+    fn test_write_parquet_codecs() {
+        let mut df = test_frame();
+        for codec in ParquetCodec::ALL {
+            let options = ParquetOptions::default().with_codec(codec);
+            assert_eq!(write(&mut df, &options).finish().unwrap(), df, "{}", codec);
+        }
+    }
+
+    #[test]
+    fn test_write_parquet_compression_levels() {
+        let mut df = test_frame();
+        for (codec, level) in [
+            (ParquetCodec::Gzip, 9),
+            (ParquetCodec::Zstd, 3),
+            (ParquetCodec::Brotli, 11),
+        ] {
+            let options = ParquetOptions::default()
+                .with_codec(codec)
+                .with_compression_level(Some(level));
+            assert_eq!(write(&mut df, &options).finish().unwrap(), df, "{}", codec);
+        }
+
+        let invalid = ParquetOptions::default()
+            .with_codec(ParquetCodec::Zstd)
+            .with_compression_level(Some(100));
+        assert!(write_parquet(&mut df, Vec::new(), &invalid, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_write_parquet_statistics() {
+        let mut df = test_frame();
+        let mut statistics = |options: &ParquetOptions| {
+            let mut reader = write(&mut df, options);
+            let metadata = reader.get_metadata().unwrap();
+            metadata.row_groups[0]
+                .columns()
+                .iter()
+                .map(|c| c.statistics().transpose().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let stats = statistics(&ParquetOptions::default());
+        let Some(Statistics::Int64(data_size)) = &stats[1] else {
+            panic!("dataSize statistics not found: {:?}", stats[1]);
+        };
+        assert_eq!(
+            (
+                data_size.min_value,
+                data_size.max_value,
+                data_size.null_count
+            ),
+            (Some(0), Some(999), Some(0))
+        );
+        assert_eq!(data_size.distinct_count, None);
+
+        let stats = statistics(&ParquetOptions::default().with_min_max_statistics(false));
+        assert!(stats.iter().all(|s| s.is_none()));
+
+        // distinct counts are only computed for booleans
+        let stats = statistics(&ParquetOptions::default().with_full_statistics());
+        let (Some(Statistics::Int64(data_size)), Some(Statistics::Boolean(included))) =
+            (&stats[1], &stats[2])
+        else {
+            panic!("statistics not found: {:?}", stats);
+        };
+        assert_eq!(data_size.distinct_count, None);
+        assert_eq!(included.distinct_count, Some(2));
+    }
+
+    #[test]
+    fn test_write_parquet_row_groups() {
+        let mut df = test_frame();
+        for (row_group_size, row_groups) in [(None, 1), (Some(300), 4), (Some(1000), 1)] {
+            let options = ParquetOptions::default()
+                .with_row_group_size(row_group_size)
+                .with_dictionary(false);
+            let mut reader = write(&mut df, &options);
+            assert_eq!(reader.get_metadata().unwrap().row_groups.len(), row_groups);
+            assert_eq!(reader.finish().unwrap(), df);
+        }
+    }
+
+    #[test]
+    fn test_timestamp_row_range() {
+        // sorted file with 100 rows per row group
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sorted.parquet");
+        let df = DataFrame::new(vec![
+            Series::new("timestamp", (0..1000i64).rev().collect::<Vec<_>>()),
+            Series::new(
+                "hash",
+                (0..1000).map(|i| format!("0x{}", i)).collect::<Vec<_>>(),
+            ),
+        ])
+        .unwrap();
+        let options = ParquetOptions::default().with_row_group_size(Some(100));
+        let (mut df, metadata) = sort_for_writing(df, &options).unwrap();
+        assert_eq!(
+            df.column("timestamp").unwrap().i64().unwrap().get(0),
+            Some(0)
+        );
+        write_parquet(
+            &mut df,
+            std::fs::File::create(&path).unwrap(),
+            &options,
+            metadata,
+        )
+        .unwrap();
+        let row_range = |range: TimeRange| timestamp_row_range(&path, &range).unwrap();
+        assert_eq!(row_range(TimeRange::new(150, 320)), Some((100, 400)));
+        assert_eq!(
+            row_range(TimeRange::new(199, 200).with_from_inclusive(false)),
+            Some((200, 200))
+        );
+        // 199 is in the second row group
+        assert_eq!(row_range(TimeRange::new(199, 200)), Some((100, 200)));
+        assert_eq!(
+            row_range(TimeRange::new(150, 200).with_to_inclusive(true)),
+            Some((100, 300))
+        );
+        assert_eq!(row_range(TimeRange::new(2000, 3000)), Some((1000, 1000)));
+
+        let unsorted = dir.path().join("unsorted.parquet");
+        write_parquet(
+            &mut df,
+            std::fs::File::create(&unsorted).unwrap(),
+            &options,
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            timestamp_row_range(&unsorted, &TimeRange::new(150, 320)).unwrap(),
+            None
+        );
+    }
+}
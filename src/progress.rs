@@ -0,0 +1,454 @@
+//! Progress events of downloads and conversions. Events are sent to the reporter of the config,
+//! or shown as indicatif progress bars if no reporter is set and the `progress` feature is enabled.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Bytes received between two [`ProgressEvent::BytesReceived`] events.
+const BYTES_INTERVAL: u64 = 1 << 20;
+
+/// Records parsed between two [`ProgressEvent::RecordsParsed`] events.
+const RECORDS_INTERVAL: u64 = 10_000;
+
+/// Progress of one file of the data directory, `file` is the written parquet file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// upstream file is downloaded from `source`, relative to the base url
+    DownloadStarted {
+        file: PathBuf,
+        source: String,
+    },
+    /// bytes of the upstream file received so far, reported every MiB and when done
+    BytesReceived {
+        file: PathBuf,
+        bytes: u64,
+    },
+    /// csv records parsed so far, reported every 10000 records and when done
+    RecordsParsed {
+        file: PathBuf,
+        records: u64,
+    },
+    FileWritten {
+        file: PathBuf,
+        bytes: u64,
+    },
+    /// file already exists and overwrite is disabled
+    Skipped {
+        file: PathBuf,
+    },
+    Failed {
+        file: PathBuf,
+        error: String,
+    },
+}
+
+impl ProgressEvent {
+    pub fn file(&self) -> &Path {
+        match self {
+            ProgressEvent::DownloadStarted { file, .. }
+            | ProgressEvent::BytesReceived { file, .. }
+            | ProgressEvent::RecordsParsed { file, .. }
+            | ProgressEvent::FileWritten { file, .. }
+            | ProgressEvent::Skipped { file }
+            | ProgressEvent::Failed { file, .. } => file,
+        }
+    }
+}
+
+pub trait ProgressReporter: Send + Sync {
+    fn report(&self, event: &ProgressEvent);
+}
+
+impl<F: Fn(&ProgressEvent) + Send + Sync> ProgressReporter for F {
+    fn report(&self, event: &ProgressEvent) {
+        self(event)
+    }
+}
+
+/// Event channel, events are dropped when the receiver is gone.
+impl ProgressReporter for mpsc::Sender<ProgressEvent> {
+    fn report(&self, event: &ProgressEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+/// One progress bar per file.
+#[cfg(feature = "progress")]
+#[derive(Default)]
+pub struct IndicatifReporter {
+    multi: indicatif::MultiProgress,
+    bars: std::sync::Mutex<std::collections::HashMap<PathBuf, indicatif::ProgressBar>>,
+}
+
+#[cfg(feature = "progress")]
+impl IndicatifReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bar(&self, file: &Path, template: &str) -> indicatif::ProgressBar {
+        let style = indicatif::ProgressStyle::default_spinner()
+            .template(template)
+            .unwrap();
+        let mut bars = self.bars.lock().expect("poisoned lock");
+        let bar = bars.entry(file.to_path_buf()).or_insert_with(|| {
+            self.multi.add(
+                indicatif::ProgressBar::new_spinner()
+                    .with_finish(indicatif::ProgressFinish::AndLeave),
+            )
+        });
+        bar.set_style(style);
+        bar.clone()
+    }
+
+    fn remove(&self, file: &Path) -> Option<indicatif::ProgressBar> {
+        self.bars.lock().expect("poisoned lock").remove(file)
+    }
+}
+
+#[cfg(feature = "progress")]
+impl ProgressReporter for IndicatifReporter {
+    fn report(&self, event: &ProgressEvent) {
+        const BYTES_TEMPLATE: &str =
+            "{spinner:.green} [{elapsed_precise}] [{bytes}]  [{bytes_per_sec}] {msg}";
+        const RECORDS_TEMPLATE: &str =
+            "{spinner:.green} [{elapsed_precise}] Records: [{pos}] {msg}";
+
+        match event {
+            ProgressEvent::DownloadStarted { file, source } => {
+                let bar = self.bar(file, BYTES_TEMPLATE);
+                bar.set_message(format!("Downloading {}", source));
+            }
+            ProgressEvent::BytesReceived { file, bytes } => {
+                self.bar(file, BYTES_TEMPLATE).set_position(*bytes);
+            }
+            ProgressEvent::RecordsParsed { file, records } => {
+                let bar = self.bar(file, RECORDS_TEMPLATE);
+                bar.set_message(format!("Converting {}", file.display()));
+                bar.set_position(*records);
+            }
+            ProgressEvent::FileWritten { file, bytes } => {
+                let bar = self.bar(file, BYTES_TEMPLATE);
+                self.remove(file);
+                bar.set_position(*bytes);
+                bar.finish_with_message(format!("Written file: {}", file.display()));
+            }
+            ProgressEvent::Skipped { .. } => {}
+            ProgressEvent::Failed { file, error } => {
+                if let Some(bar) = self.remove(file) {
+                    bar.abandon_with_message(format!("Failed {}: {}", file.display(), error));
+                }
+            }
+        }
+    }
+}
+
+// progress bars shared by all configs with progress enabled
+pub(crate) fn default_reporter() -> Option<&'static dyn ProgressReporter> {
+    #[cfg(feature = "progress")]
+    {
+        static REPORTER: std::sync::OnceLock<IndicatifReporter> = std::sync::OnceLock::new();
+        Some(REPORTER.get_or_init(IndicatifReporter::new))
+    }
+    #[cfg(not(feature = "progress"))]
+    None
+}
+
+// reporter with the file the events are about
+#[derive(Clone, Copy)]
+pub(crate) struct FileProgress<'a> {
+    reporter: Option<&'a dyn ProgressReporter>,
+    file: &'a Path,
+}
+
+impl<'a> FileProgress<'a> {
+    pub(crate) fn new(reporter: Option<&'a dyn ProgressReporter>, file: &'a Path) -> Self {
+        Self { reporter, file }
+    }
+
+    pub(crate) fn report(&self, event: impl FnOnce(PathBuf) -> ProgressEvent) {
+        if let Some(reporter) = self.reporter {
+            reporter.report(&event(self.file.to_path_buf()));
+        }
+    }
+
+    pub(crate) fn report_result<T>(&self, result: eyre::Result<T>) -> eyre::Result<T> {
+        if let Err(e) = &result {
+            self.report(|file| ProgressEvent::Failed {
+                file,
+                error: e.to_string(),
+            });
+        }
+        result
+    }
+
+    pub(crate) fn written(&self) -> eyre::Result<()> {
+        if self.reporter.is_some() {
+            let bytes = std::fs::metadata(self.file)?.len();
+            self.report(|file| ProgressEvent::FileWritten { file, bytes });
+        }
+        Ok(())
+    }
+}
+
+// reports received bytes
+pub(crate) struct BytesProgress<'a> {
+    progress: FileProgress<'a>,
+    bytes: u64,
+    reported: u64,
+}
+
+impl<'a> BytesProgress<'a> {
+    pub(crate) fn new(progress: FileProgress<'a>) -> Self {
+        Self {
+            progress,
+            bytes: 0,
+            reported: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, n: usize) {
+        self.bytes += n as u64;
+        if self.bytes - self.reported >= BYTES_INTERVAL {
+            self.finish();
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        if self.bytes > self.reported {
+            self.reported = self.bytes;
+            let bytes = self.bytes;
+            self.progress
+                .report(|file| ProgressEvent::BytesReceived { file, bytes });
+        }
+    }
+}
+
+// reports received bytes of the reader
+pub(crate) struct ProgressRead<'a, R> {
+    inner: R,
+    progress: BytesProgress<'a>,
+}
+
+impl<'a, R> ProgressRead<'a, R> {
+    pub(crate) fn new(inner: R, progress: FileProgress<'a>) -> Self {
+        Self {
+            inner,
+            progress: BytesProgress::new(progress),
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressRead<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 {
+            self.progress.finish();
+        } else {
+            self.progress.add(n);
+        }
+        Ok(n)
+    }
+}
+
+// reports parsed records
+pub(crate) struct RecordsProgress<'a> {
+    progress: FileProgress<'a>,
+    records: u64,
+}
+
+impl<'a> RecordsProgress<'a> {
+    pub(crate) fn new(progress: FileProgress<'a>) -> Self {
+        Self {
+            progress,
+            records: 0,
+        }
+    }
+
+    pub(crate) fn inc(&mut self) {
+        self.records += 1;
+        if self.records.is_multiple_of(RECORDS_INTERVAL) {
+            self.finish();
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        let records = self.records;
+        self.progress
+            .report(|file| ProgressEvent::RecordsParsed { file, records });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::MemoryFetcher;
+    use crate::test_utils::sourcelog_zip;
+    use crate::{Config, DataKind};
+
+    #[test]
+    fn test_bytes_received_every_interval() {
+        let (events, received) = mpsc::channel();
+        let mut progress = BytesProgress::new(FileProgress::new(Some(&events), Path::new("a")));
+        let received = || {
+            received
+                .try_iter()
+                .map(|e| match e {
+                    ProgressEvent::BytesReceived { bytes, .. } => bytes,
+                    e => panic!("unexpected event {:?}", e),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        progress.add(BYTES_INTERVAL as usize - 1);
+        assert!(received().is_empty());
+        progress.add(1);
+        assert_eq!(received(), vec![BYTES_INTERVAL]);
+        progress.add(10);
+        assert!(received().is_empty());
+        progress.finish();
+        progress.finish();
+        assert_eq!(received(), vec![BYTES_INTERVAL + 10]);
+    }
+
+    #[test]
+    fn test_records_parsed_every_interval() {
+        let (events, received) = mpsc::channel();
+        let mut progress = RecordsProgress::new(FileProgress::new(Some(&events), Path::new("a")));
+        for _ in 0..2 * RECORDS_INTERVAL + 5 {
+            progress.inc();
+        }
+        progress.finish();
+        let records = received
+            .try_iter()
+            .map(|e| match e {
+                ProgressEvent::RecordsParsed { records, .. } => records,
+                e => panic!("unexpected event {:?}", e),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                RECORDS_INTERVAL,
+                2 * RECORDS_INTERVAL,
+                2 * RECORDS_INTERVAL + 5
+            ]
+        );
+    }
+
+    #[test]
+    fn test_download_events() {
+        let fetcher = MemoryFetcher::new();
+        fetcher.insert(
+            "2023-09/2023-09-01_sourcelog.csv.zip",
+            sourcelog_zip("2023-09-01", &[1_693_526_400_001]),
+        );
+        fetcher.insert("2023-09/2023-09-02_sourcelog.csv.zip", "not a zip");
+        let data_dir = tempfile::tempdir().unwrap();
+        let (events, received) = mpsc::channel();
+        let config = Config::new(data_dir.path())
+            .with_fetcher(fetcher)
+            .with_reporter(events);
+
+        config.download_sourcelog_file("2023-09-01").unwrap();
+        let file = DataKind::Sourcelog.path(data_dir.path(), "2023-09-01");
+        let events = received.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            ProgressEvent::DownloadStarted {
+                file: file.clone(),
+                source: "2023-09/2023-09-01_sourcelog.csv.zip".to_string(),
+            }
+        );
+        assert!(matches!(events[1], ProgressEvent::BytesReceived { bytes, .. } if bytes > 0));
+        assert_eq!(
+            events[2],
+            ProgressEvent::RecordsParsed {
+                file: file.clone(),
+                records: 1,
+            }
+        );
+        assert!(matches!(events[3], ProgressEvent::FileWritten { bytes, .. } if bytes > 0));
+        assert!(events.iter().all(|e| e.file() == file));
+
+        config.download_sourcelog_file("2023-09-01").unwrap();
+        assert_eq!(
+            received.try_iter().collect::<Vec<_>>(),
+            vec![ProgressEvent::Skipped { file }]
+        );
+
+        assert!(config.download_sourcelog_file("2023-09-02").is_err());
+        assert!(matches!(
+            received.try_iter().last(),
+            Some(ProgressEvent::Failed { file, .. })
+                if file == DataKind::Sourcelog.path(data_dir.path(), "2023-09-02")
+        ));
+    }
+
+    #[cfg(feature = "progress")]
+    #[test]
+    fn test_indicatif_reporter() {
+        let reporter = IndicatifReporter {
+            multi: indicatif::MultiProgress::with_draw_target(
+                indicatif::ProgressDrawTarget::hidden(),
+            ),
+            ..Default::default()
+        };
+        let file = PathBuf::from("sourcelog/2023-09-01_sourcelog.parquet");
+        let bar = |file: &Path| reporter.bars.lock().unwrap().get(file).cloned();
+
+        reporter.report(&ProgressEvent::DownloadStarted {
+            file: file.clone(),
+            source: "2023-09/2023-09-01_sourcelog.csv.zip".to_string(),
+        });
+        let download = bar(&file).unwrap();
+        assert_eq!(
+            download.message(),
+            "Downloading 2023-09/2023-09-01_sourcelog.csv.zip"
+        );
+        reporter.report(&ProgressEvent::BytesReceived {
+            file: file.clone(),
+            bytes: 100,
+        });
+        assert_eq!(download.position(), 100);
+
+        // the same bar shows the conversion
+        reporter.report(&ProgressEvent::RecordsParsed {
+            file: file.clone(),
+            records: 10,
+        });
+        assert_eq!(download.position(), 10);
+        assert!(download.message().starts_with("Converting"));
+
+        reporter.report(&ProgressEvent::FileWritten {
+            file: file.clone(),
+            bytes: 2000,
+        });
+        assert!(download.is_finished());
+        assert_eq!(download.position(), 2000);
+        assert!(bar(&file).is_none());
+
+        let failed = PathBuf::from("sourcelog/2023-09-02_sourcelog.parquet");
+        reporter.report(&ProgressEvent::DownloadStarted {
+            file: failed.clone(),
+            source: "2023-09/2023-09-02_sourcelog.csv.zip".to_string(),
+        });
+        let download = bar(&failed).unwrap();
+        reporter.report(&ProgressEvent::Failed {
+            file: failed.clone(),
+            error: "not a zip".to_string(),
+        });
+        assert!(download.is_finished());
+        assert!(bar(&failed).is_none());
+
+        // events of skipped files don't create bars
+        reporter.report(&ProgressEvent::Skipped { file: file.clone() });
+        reporter.report(&ProgressEvent::Failed {
+            file,
+            error: "error".to_string(),
+        });
+        assert!(reporter.bars.lock().unwrap().is_empty());
+    }
+}
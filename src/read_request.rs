@@ -0,0 +1,623 @@
+//! Reads of the day files with column projection and filters. A [`ReadRequest`] compiles to one
+//! polars `LazyFrame`, so only the needed columns are read and filters are applied while the day
+//! files are scanned.
+
+use crate::reader::{
+    day_paths, days_in_range, merge_sorted, raw_transactions_from_dataframe, scan_day_file,
+    transaction_data_from_dataframe,
+};
+use crate::{DataKind, RawTransaction, TimeRange, TransactionData, TransactionRangeError};
+use polars::frame::DataFrame;
+use polars::prelude::{
+    col, concat_lf_diagonal, lit, DataType, Expr, IntoLazy, JoinArgs, JoinType, LazyFrame,
+    NamedFrom, Series, SortMultipleOptions, TimeUnit, UnionArgs,
+};
+use std::path::{Path, PathBuf};
+
+/// Gas columns of transaction-data, stored as base 10 strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GasColumn {
+    /// gas limit
+    Gas,
+    GasPrice,
+    GasTipCap,
+    GasFeeCap,
+}
+
+impl GasColumn {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GasColumn::Gas => "gas",
+            GasColumn::GasPrice => "gasPrice",
+            GasColumn::GasTipCap => "gasTipCap",
+            GasColumn::GasFeeCap => "gasFeeCap",
+        }
+    }
+}
+
+/// Row filter of a [`ReadRequest`]. Addresses, hashes and selectors are compared ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// sender is one of the addresses
+    From(Vec<String>),
+    /// receiver is one of the addresses
+    To(Vec<String>),
+    /// sender or receiver is one of the addresses
+    Address(Vec<String>),
+    Hash(Vec<String>),
+    /// first 4 bytes of the call data, e.g. `0xa9059cbb`
+    Selector(Vec<String>),
+    /// `source` of sourcelog, or one of the `sources` of transaction-data of the newer layouts.
+    /// Fails with [`TransactionRangeError::MissingColumn`] on transaction-data of the older layouts
+    /// unless sourcelog is one of the kinds.
+    Source(Vec<String>),
+    /// value of the gas column in `[min, max]`, values that don't fit in u64 don't match
+    Gas {
+        column: GasColumn,
+        min: Option<u64>,
+        max: Option<u64>,
+    },
+}
+
+impl Filter {
+    // columns are the columns of the frame the filter is applied to
+    fn expr(&self, columns: &[String]) -> Result<Expr, TransactionRangeError> {
+        // stored values can be checksummed or uppercase
+        let is_in = |column: &str, values: &[String]| {
+            let values = values.iter().map(|v| v.to_lowercase()).collect::<Vec<_>>();
+            col(column)
+                .str()
+                .to_lowercase()
+                .is_in(lit(Series::new("", values)))
+        };
+        let has_column = |column: &str| columns.iter().any(|c| c == column);
+        Ok(match self {
+            Filter::From(addresses) => is_in("from", addresses),
+            Filter::To(addresses) => is_in("to", addresses),
+            Filter::Address(addresses) => is_in("from", addresses).or(is_in("to", addresses)),
+            Filter::Hash(hashes) => is_in("hash", hashes),
+            Filter::Selector(selectors) => is_in("data4Bytes", selectors),
+            Filter::Source(sources) if has_column("source") => {
+                col("source").is_in(lit(Series::new("", sources.clone())))
+            }
+            Filter::Source(sources) if has_column("sources") => sources
+                .iter()
+                .map(|source| col("sources").list().contains(lit(source.as_str())))
+                .reduce(Expr::or)
+                .unwrap_or(lit(false)),
+            // transaction-data of the older layouts, sourcelog has to be joined
+            Filter::Source(_) => {
+                return Err(TransactionRangeError::MissingColumn("sources".to_string()))
+            }
+            Filter::Gas { column, min, max } => {
+                // unparsable values are null and don't match
+                let value = col(column.name()).cast(DataType::UInt64);
+                let mut expr = value.clone().is_not_null();
+                if let Some(min) = min {
+                    expr = expr.and(value.clone().gt_eq(lit(*min)));
+                }
+                if let Some(max) = max {
+                    expr = expr.and(value.lt_eq(lit(*max)));
+                }
+                expr
+            }
+        })
+    }
+}
+
+/// Rows of the time range of one or more kinds. Rows of the first kind are joined with the rows
+/// of the other kinds on `hash`, e.g. transaction-data with sourcelog gives one row per
+/// transaction and source. All columns are read if no columns are set, an empty range gives an
+/// empty frame with the set columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadRequest {
+    /// transaction-data if empty, or transactions if `rawTx` is one of the columns
+    pub kinds: Vec<DataKind>,
+    pub columns: Vec<String>,
+    pub range: TimeRange,
+    pub filters: Vec<Filter>,
+}
+
+impl ReadRequest {
+    pub fn new(range: impl Into<TimeRange>) -> Self {
+        Self {
+            kinds: Vec::new(),
+            columns: Vec::new(),
+            range: range.into(),
+            filters: Vec::new(),
+        }
+    }
+
+    pub fn with_kinds(mut self, kinds: Vec<DataKind>) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    pub fn with_columns(mut self, columns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.columns = columns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    fn kinds(&self) -> Vec<DataKind> {
+        if !self.kinds.is_empty() {
+            self.kinds.clone()
+        } else if self.columns.iter().any(|c| c == "rawTx") {
+            vec![DataKind::Transactions]
+        } else {
+            vec![DataKind::TransactionData]
+        }
+    }
+
+    /// Lazy frame of the request sorted by timestamp, fails if a day file is missing.
+    pub fn lazy_frame(
+        &self,
+        data_dir: impl AsRef<Path>,
+    ) -> Result<LazyFrame, TransactionRangeError> {
+        let days = days_in_range(&self.range)?;
+        let mut lf = self.compile(data_dir.as_ref(), &self.kinds(), &days, &days)?;
+        if !self.columns.is_empty() {
+            lf = lf.select(self.columns.iter().map(|c| col(c)).collect::<Vec<_>>());
+        }
+        Ok(lf)
+    }
+
+    pub fn dataframe(
+        &self,
+        data_dir: impl AsRef<Path>,
+    ) -> Result<DataFrame, TransactionRangeError> {
+        Ok(self.lazy_frame(data_dir)?.collect()?)
+    }
+
+    /// Transaction data of the request, the columns are ignored. Rows are read one day at a time
+    /// and joined with the rows of the other kinds of the same and the neighbouring days, so rows
+    /// of the same hash more than a day apart are joined by [`Self::dataframe`] only.
+    pub fn transaction_data(
+        &self,
+        data_dir: impl AsRef<Path>,
+    ) -> Result<Rows<TransactionData>, TransactionRangeError> {
+        self.rows(
+            data_dir.as_ref(),
+            DataKind::TransactionData,
+            transaction_data_from_dataframe,
+            |r| r.timestamp_ms,
+        )
+    }
+
+    /// Raw transactions of the request, the columns are ignored, see [`Self::transaction_data`].
+    pub fn raw_transactions(
+        &self,
+        data_dir: impl AsRef<Path>,
+    ) -> Result<Rows<RawTransaction>, TransactionRangeError> {
+        self.rows(
+            data_dir.as_ref(),
+            DataKind::Transactions,
+            raw_transactions_from_dataframe,
+            |r| r.timestamp_ms,
+        )
+    }
+
+    fn rows<T>(
+        &self,
+        data_dir: &Path,
+        default_kind: DataKind,
+        convert: fn(&DataFrame) -> Result<Vec<T>, TransactionRangeError>,
+        key: fn(&T) -> i64,
+    ) -> Result<Rows<T>, TransactionRangeError> {
+        let kinds = if self.kinds.is_empty() {
+            vec![default_kind]
+        } else {
+            self.kinds.clone()
+        };
+        let days = days_in_range(&self.range)?;
+        // fail before the first row if a day file is missing
+        for kind in &kinds {
+            day_paths(data_dir, *kind, &days)?;
+        }
+        Ok(Rows {
+            request: self.clone(),
+            kinds,
+            data_dir: data_dir.to_path_buf(),
+            days,
+            next_day: 0,
+            convert,
+            key,
+            ready: Vec::new().into_iter(),
+            pending: Vec::new(),
+            done: false,
+        })
+    }
+
+    // scans of the days of the first kind joined on hash with the scans of the join days of the
+    // other kinds, filtered
+    fn compile(
+        &self,
+        data_dir: &Path,
+        kinds: &[DataKind],
+        days: &[String],
+        join_days: &[String],
+    ) -> Result<LazyFrame, TransactionRangeError> {
+        if days.is_empty() {
+            return self.empty_frame();
+        }
+        let mut frames = Vec::new();
+        for (i, kind) in kinds.iter().enumerate() {
+            let days = if i == 0 { days } else { join_days };
+            let scans = day_paths(data_dir, *kind, days)?
+                .into_iter()
+                .map(|path| scan_day_file(path, &self.range))
+                .collect::<Result<Vec<_>, _>>()?;
+            // files converted from older upstream layouts don't have the newer columns
+            frames.push(concat_lf_diagonal(scans, UnionArgs::default())?);
+        }
+
+        let mut frames = frames.into_iter();
+        let Some(mut lf) = frames.next() else {
+            return self.empty_frame();
+        };
+        let mut columns = column_names(&mut lf)?;
+        for mut other in frames {
+            let other_columns = column_names(&mut other)?
+                .into_iter()
+                .filter(|c| !columns.contains(c))
+                .collect::<Vec<_>>();
+            let select = std::iter::once(col("hash"))
+                .chain(other_columns.iter().map(|c| col(c)))
+                .collect::<Vec<_>>();
+            lf = lf.join(
+                other.select(select),
+                [col("hash")],
+                [col("hash")],
+                JoinArgs::new(JoinType::Inner),
+            );
+            columns.extend(other_columns);
+        }
+
+        for filter in &self.filters {
+            lf = lf.filter(filter.expr(&columns)?);
+        }
+        // days can overlap at the edges and joins don't keep the order
+        Ok(lf.sort(
+            ["timestamp"],
+            SortMultipleOptions::default().with_maintain_order(true),
+        ))
+    }
+
+    // no rows, with the columns of the request
+    fn empty_frame(&self) -> Result<LazyFrame, TransactionRangeError> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| Series::new_empty(c, &column_type(c)))
+            .collect::<Vec<_>>();
+        Ok(DataFrame::new(columns)?.lazy())
+    }
+}
+
+fn column_names(lf: &mut LazyFrame) -> Result<Vec<String>, TransactionRangeError> {
+    Ok(lf.schema()?.iter_names().map(|c| c.to_string()).collect())
+}
+
+// type of the column in the day files, other columns are strings
+fn column_type(column: &str) -> DataType {
+    match column {
+        "timestamp" => DataType::Datetime(TimeUnit::Milliseconds, None),
+        "dataSize"
+        | "includedAtBlockHeight"
+        | "includedBlockTimestamp"
+        | "inclusionDelayMs"
+        | "txType" => DataType::Int64,
+        "sources" | "blobHashes" => DataType::List(Box::new(DataType::String)),
+        _ => DataType::String,
+    }
+}
+
+/// Rows of a [`ReadRequest`] sorted by timestamp, day files are read one at a time. Day files can
+/// overlap at the edges so rows of a day are held back until the next day is read.
+pub struct Rows<T> {
+    request: ReadRequest,
+    kinds: Vec<DataKind>,
+    data_dir: PathBuf,
+    days: Vec<String>,
+    next_day: usize,
+    convert: fn(&DataFrame) -> Result<Vec<T>, TransactionRangeError>,
+    key: fn(&T) -> i64,
+    ready: std::vec::IntoIter<T>,
+    pending: Vec<T>,
+    done: bool,
+}
+
+impl<T> Rows<T> {
+    // rows of the day joined with the rows of the same and the neighbouring days
+    fn read_day(&self, i: usize) -> Result<Vec<T>, TransactionRangeError> {
+        let join_days = &self.days[i.saturating_sub(1)..(i + 2).min(self.days.len())];
+        let df = self
+            .request
+            .compile(&self.data_dir, &self.kinds, &self.days[i..=i], join_days)?
+            .collect()?;
+        (self.convert)(&df)
+    }
+}
+
+impl<T> Iterator for Rows<T> {
+    type Item = Result<T, TransactionRangeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.ready.next() {
+                return Some(Ok(row));
+            }
+            if self.done {
+                return None;
+            }
+            if self.next_day == self.days.len() {
+                self.done = true;
+                self.ready = std::mem::take(&mut self.pending).into_iter();
+                continue;
+            }
+            let rows = match self.read_day(self.next_day) {
+                Ok(rows) => {
+                    self.next_day += 1;
+                    rows
+                }
+                Err(e) => {
+                    self.done = true;
+                    self.pending.clear();
+                    return Some(Err(e));
+                }
+            };
+            // pending rows before the first row of the day are final
+            let key = self.key;
+            let split = match rows.first() {
+                Some(first) => self.pending.partition_point(|r| key(r) < key(first)),
+                None => 0,
+            };
+            let rest = self.pending.split_off(split);
+            let pending = merge_sorted(vec![rest, rows], key);
+            self.ready = std::mem::replace(&mut self.pending, pending).into_iter();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "download"))]
+mod tests {
+    use super::*;
+    use crate::get_transaction_data;
+    use crate::test_utils::{transaction_data_frame, tx, write_sourcelog, write_transaction_data};
+
+    // 2023-09-01 and 2023-09-02 00:00:00
+    const DAY1: i64 = 1_693_526_400_000;
+    const DAY2: i64 = 1_693_612_800_000;
+    const RANGE: (i64, i64) = (DAY1, DAY2 + 100);
+
+    fn transfer(timestamp_ms: i64, hash: &str, from: &str, gas_price: &str) -> TransactionData {
+        tx(timestamp_ms, hash)
+            .with_from(from)
+            .with_to("0xBB")
+            .with_gas_price(gas_price)
+            .with_data_4bytes("0xa9059cbb")
+            .with_sources(&["local"])
+    }
+
+    // 0x03 is seen after the first row of the next day, its sourcelog row is in the next day file
+    fn write_days(data_dir: &Path) {
+        write_transaction_data(
+            data_dir,
+            "2023-09-01",
+            &[
+                transfer(DAY1 + 10, "0x01", "0xAA", "10"),
+                transfer(DAY1 + 20, "0x02", "0xCC", "20"),
+                transfer(DAY2 + 5, "0x03", "0xAA", "30"),
+            ],
+        );
+        let mut unknown_source =
+            transfer(DAY2 + 1, "0x04", "0xAA", "not a number").with_data_4bytes("0x");
+        unknown_source.sources = None;
+        write_transaction_data(
+            data_dir,
+            "2023-09-02",
+            &[unknown_source, transfer(DAY2 + 10, "0x05", "0xCC", "50")],
+        );
+        write_sourcelog(
+            data_dir,
+            "2023-09-01",
+            &[
+                (DAY1 + 10, "0x01", "local"),
+                (DAY1 + 11, "0x01", "bloxroute"),
+            ],
+        );
+        write_sourcelog(
+            data_dir,
+            "2023-09-02",
+            &[(DAY2 + 1, "0x04", "bloxroute"), (DAY2 + 6, "0x03", "local")],
+        );
+    }
+
+    fn hashes(df: &DataFrame) -> Vec<String> {
+        df.column("hash")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .map(|h| h.to_string())
+            .collect()
+    }
+
+    fn read_hashes(data_dir: &Path, request: ReadRequest) -> Vec<String> {
+        hashes(&request.dataframe(data_dir).unwrap())
+    }
+
+    #[test]
+    fn test_columns_and_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let df = ReadRequest::new(RANGE)
+            .with_columns(["hash", "gasPrice"])
+            .dataframe(dir.path())
+            .unwrap();
+        assert_eq!(df.get_column_names(), vec!["hash", "gasPrice"]);
+        assert_eq!(hashes(&df), vec!["0x01", "0x02", "0x04", "0x03", "0x05"]);
+    }
+
+    #[test]
+    fn test_empty_range_keeps_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let request =
+            ReadRequest::new(RANGE).with_columns(["timestamp", "hash", "dataSize", "sources"]);
+        let schema = request.dataframe(dir.path()).unwrap().schema();
+        let df = ReadRequest {
+            range: TimeRange::new(DAY1, DAY1),
+            ..request
+        }
+        .dataframe(dir.path())
+        .unwrap();
+        assert_eq!(df.height(), 0);
+        assert_eq!(df.schema(), schema);
+    }
+
+    #[test]
+    fn test_address_and_gas_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let request = ReadRequest::new(RANGE)
+            .with_columns(["hash"])
+            .with_filter(Filter::From(vec!["0xAA".to_string()]));
+        assert_eq!(
+            read_hashes(dir.path(), request.clone()),
+            vec!["0x01", "0x04", "0x03"]
+        );
+        let request = request.with_filter(Filter::Gas {
+            column: GasColumn::GasPrice,
+            min: Some(15),
+            max: None,
+        });
+        assert_eq!(read_hashes(dir.path(), request), vec!["0x03"]);
+    }
+
+    #[test]
+    fn test_selector_and_sources_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let request = ReadRequest::new(RANGE)
+            .with_filter(Filter::Selector(vec!["0xA9059CBB".to_string()]))
+            .with_filter(Filter::Source(vec!["local".to_string()]));
+        assert_eq!(
+            read_hashes(dir.path(), request),
+            vec!["0x01", "0x02", "0x03", "0x05"]
+        );
+    }
+
+    #[test]
+    fn test_filters_ignore_case_of_stored_values() {
+        let dir = tempfile::tempdir().unwrap();
+        write_transaction_data(
+            dir.path(),
+            "2023-09-01",
+            &[
+                transfer(DAY1 + 10, "0xAbCd", "0xAA", "10").with_data_4bytes("0xA9059CBB"),
+                transfer(DAY1 + 20, "0xef01", "0xAA", "10").with_data_4bytes("0x095ea7b3"),
+            ],
+        );
+
+        let request = ReadRequest::new((DAY1, DAY1 + 100))
+            .with_filter(Filter::Hash(vec![
+                "0xABCD".to_string(),
+                "0xEF01".to_string(),
+            ]))
+            .with_filter(Filter::Selector(vec!["0xa9059cbb".to_string()]));
+        assert_eq!(read_hashes(dir.path(), request), vec!["0xAbCd"]);
+    }
+
+    #[test]
+    fn test_sources_filter_without_sources_column() {
+        let dir = tempfile::tempdir().unwrap();
+        // older layouts don't have the sources column
+        let df = transaction_data_frame(&[transfer(DAY1 + 10, "0x01", "0xAA", "10")])
+            .drop("sources")
+            .unwrap();
+        let path = crate::path_transaction_data(dir.path(), "2023-09-01");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        crate::write_dataframe_to_parquet(df, path, &crate::ParquetOptions::default()).unwrap();
+
+        let err = ReadRequest::new((DAY1, DAY1 + 100))
+            .with_filter(Filter::Source(vec!["local".to_string()]))
+            .dataframe(dir.path())
+            .unwrap_err();
+        assert!(matches!(err, TransactionRangeError::MissingColumn(column) if column == "sources"));
+    }
+
+    #[test]
+    fn test_join_with_sourcelog() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let df = ReadRequest::new(RANGE)
+            .with_kinds(vec![DataKind::TransactionData, DataKind::Sourcelog])
+            .with_columns(["hash", "source"])
+            .with_filter(Filter::Source(vec!["bloxroute".to_string()]))
+            .dataframe(dir.path())
+            .unwrap();
+        assert_eq!(df.shape(), (2, 2));
+        assert_eq!(hashes(&df), vec!["0x01", "0x04"]);
+    }
+
+    #[test]
+    fn test_rows_match_dataframe() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let rows = ReadRequest::new(RANGE)
+            .transaction_data(dir.path())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows, get_transaction_data(dir.path(), RANGE).unwrap());
+
+        let rows = ReadRequest::new(RANGE)
+            .with_filter(Filter::Address(vec!["0xbb".to_string()]))
+            .with_filter(Filter::Hash(vec!["0x05".to_string()]))
+            .transaction_data(dir.path())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].from, "0xcc");
+    }
+
+    #[test]
+    fn test_rows_join_neighbouring_days() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        let request = ReadRequest::new(RANGE)
+            .with_kinds(vec![DataKind::TransactionData, DataKind::Sourcelog]);
+        let rows = request
+            .transaction_data(dir.path())
+            .unwrap()
+            .map(|row| row.map(|r| r.hash))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows, vec!["0x01", "0x01", "0x04", "0x03"]);
+        assert_eq!(rows, read_hashes(dir.path(), request));
+    }
+
+    #[test]
+    fn test_missing_day_file() {
+        let dir = tempfile::tempdir().unwrap();
+        write_days(dir.path());
+
+        assert!(matches!(
+            ReadRequest::new(RANGE).raw_transactions(dir.path()).err(),
+            Some(TransactionRangeError::DayFileNotFound(_))
+        ));
+    }
+}
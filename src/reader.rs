@@ -0,0 +1,482 @@
+use crate::coverage::CoverageReport;
+use crate::data_dir::existing_day_path;
+use crate::{
+    naive_datetime_from_millis, parquet, DataKind, RawTransaction, TimeRange, TransactionData,
+    TransactionRangeError,
+};
+use polars::error::PolarsError;
+use polars::frame::DataFrame;
+use polars::prelude::{col, IdxSize, LazyFrame, ScanArgsParquet, Series, SortMultipleOptions};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+
+/// Reads all columns of the files of the given kind for the time range into one dataframe
+/// sorted by timestamp. A `(from_ms, to_ms)` range is `[from_ms, to_ms)`, see [`TimeRange`].
+pub fn get_dataframe(
+    data_dir: impl AsRef<Path>,
+    kind: DataKind,
+    range: impl Into<TimeRange>,
+) -> Result<DataFrame, TransactionRangeError> {
+    read_dataframe(data_dir.as_ref(), kind, &range.into(), false).map(|(df, _)| df)
+}
+
+/// Like [`get_dataframe`], but days without a file are skipped and reported in the coverage.
+pub fn get_dataframe_with_coverage(
+    data_dir: impl AsRef<Path>,
+    kind: DataKind,
+    range: impl Into<TimeRange>,
+) -> Result<(DataFrame, CoverageReport), TransactionRangeError> {
+    read_dataframe(data_dir.as_ref(), kind, &range.into(), true)
+}
+
+pub(crate) fn read_dataframe(
+    data_dir: &Path,
+    kind: DataKind,
+    range: &TimeRange,
+    skip_missing: bool,
+) -> Result<(DataFrame, CoverageReport), TransactionRangeError> {
+    let days = days_in_range(range)?;
+
+    tracing::trace!("Fetching {} for days: {:?}", kind, days);
+
+    let paths = find_day_paths(data_dir, kind, days, skip_missing)?;
+
+    let timestamps = |df: &DataFrame| -> Result<Option<(i64, i64)>, PolarsError> {
+        if df.height() == 0 {
+            return Ok(None);
+        }
+        let timestamp = df.column("timestamp")?.datetime()?;
+        Ok(timestamp.get(0).zip(timestamp.get(df.height() - 1)))
+    };
+    let mut coverage = CoverageReport::new(kind, *range);
+    let mut frames: Vec<DataFrame> = Vec::new();
+    // day files are sorted, only sort if days overlap
+    let mut overlap = false;
+    let mut last_max = None;
+    for (day, path) in paths {
+        let Some(path) = path else {
+            coverage.add_missing(&day);
+            continue;
+        };
+        let df = scan_day_file(path, range)?.collect()?;
+        let day_timestamps = timestamps(&df)?;
+        if let Some((min, max)) = day_timestamps {
+            overlap |= last_max.is_some_and(|last_max| min < last_max);
+            last_max = Some(max);
+        }
+        coverage.add(&day, day_timestamps);
+        frames.push(df);
+    }
+
+    // files converted from older upstream layouts don't have the newer columns
+    let mut df = if frames.is_empty() {
+        DataFrame::default()
+    } else {
+        polars::functions::concat_df_diagonal(&frames)?
+    };
+    if overlap {
+        df = df.sort(
+            ["timestamp"],
+            SortMultipleOptions::default().with_maintain_order(true),
+        )?;
+    }
+    df.as_single_chunk_par();
+    Ok((df, coverage))
+}
+
+/// Reads the raw transactions of the time range sorted by timestamp. A `(from_ms, to_ms)` range
+/// is `[from_ms, to_ms)`, older versions excluded `from_ms` too, see [`TimeRange::exclusive`].
+pub fn get_raw_transactions(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+) -> Result<Vec<RawTransaction>, TransactionRangeError> {
+    read_raw_transactions(data_dir.as_ref(), &range.into(), false)
+        .map(|(raw_transactions, _)| raw_transactions)
+}
+
+/// Like [`get_raw_transactions`], but days without a file are skipped and reported in the
+/// coverage.
+pub fn get_raw_transactions_with_coverage(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+) -> Result<(Vec<RawTransaction>, CoverageReport), TransactionRangeError> {
+    read_raw_transactions(data_dir.as_ref(), &range.into(), true)
+}
+
+pub(crate) fn read_raw_transactions(
+    data_dir: &Path,
+    range: &TimeRange,
+    skip_missing: bool,
+) -> Result<(Vec<RawTransaction>, CoverageReport), TransactionRangeError> {
+    read_days(
+        data_dir,
+        DataKind::Transactions,
+        range,
+        skip_missing,
+        read_raw_transactions_day,
+        |r| r.timestamp_ms,
+    )
+}
+
+// raw transactions of one day file in the time range sorted by timestamp
+pub(crate) fn read_raw_transactions_day(
+    path: impl AsRef<Path>,
+    range: &TimeRange,
+) -> Result<Vec<RawTransaction>, TransactionRangeError> {
+    let result = scan_day_file(path, range)?
+        .select(&[col("timestamp"), col("rawTx")])
+        .collect()?;
+    raw_transactions_from_dataframe(&result)
+}
+
+// raw transactions of the `timestamp` and `rawTx` columns
+pub(crate) fn raw_transactions_from_dataframe(
+    result: &DataFrame,
+) -> Result<Vec<RawTransaction>, TransactionRangeError> {
+    let mut raw_transactions = Vec::with_capacity(result.height());
+    let raw_tx_column = result.column("rawTx")?.binary()?;
+    let timestamp_column = result.column("timestamp")?.datetime()?;
+
+    for i in 0..raw_tx_column.len() {
+        let bytes = raw_tx_column.get(i).ok_or_else(|| {
+            TransactionRangeError::PolarsError(PolarsError::NoData("rawTx".into()))
+        })?;
+        let timestamp = timestamp_column.get(i).ok_or_else(|| {
+            TransactionRangeError::PolarsError(PolarsError::NoData("timestamp".into()))
+        })?;
+
+        raw_transactions.push(RawTransaction {
+            timestamp_ms: timestamp,
+            raw_tx: bytes.to_vec(),
+        })
+    }
+    Ok(raw_transactions)
+}
+
+/// Reads the transaction-data rows of the time range sorted by timestamp. A `(from_ms, to_ms)`
+/// range is `[from_ms, to_ms)`, older versions excluded `from_ms` too, see [`TimeRange::exclusive`].
+pub fn get_transaction_data(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+) -> Result<Vec<TransactionData>, TransactionRangeError> {
+    read_transaction_data(data_dir.as_ref(), &range.into(), false)
+        .map(|(transactions, _)| transactions)
+}
+
+/// Like [`get_transaction_data`], but days without a file are skipped and reported in the
+/// coverage.
+pub fn get_transaction_data_with_coverage(
+    data_dir: impl AsRef<Path>,
+    range: impl Into<TimeRange>,
+) -> Result<(Vec<TransactionData>, CoverageReport), TransactionRangeError> {
+    read_transaction_data(data_dir.as_ref(), &range.into(), true)
+}
+
+pub(crate) fn read_transaction_data(
+    data_dir: &Path,
+    range: &TimeRange,
+    skip_missing: bool,
+) -> Result<(Vec<TransactionData>, CoverageReport), TransactionRangeError> {
+    read_days(
+        data_dir,
+        DataKind::TransactionData,
+        range,
+        skip_missing,
+        read_transaction_data_day,
+        |r| r.timestamp_ms,
+    )
+}
+
+// reads the day files of the range and merges the sorted days
+fn read_days<T>(
+    data_dir: &Path,
+    kind: DataKind,
+    range: &TimeRange,
+    skip_missing: bool,
+    read_day: impl Fn(PathBuf, &TimeRange) -> Result<Vec<T>, TransactionRangeError>,
+    key: impl Fn(&T) -> i64,
+) -> Result<(Vec<T>, CoverageReport), TransactionRangeError> {
+    let days = days_in_range(range)?;
+
+    tracing::trace!("Fetching {} for days: {:?}", kind, days);
+
+    let paths = find_day_paths(data_dir, kind, days, skip_missing)?;
+
+    let mut coverage = CoverageReport::new(kind, *range);
+    let mut days_rows = Vec::new();
+    for (day, path) in paths {
+        let Some(path) = path else {
+            coverage.add_missing(&day);
+            continue;
+        };
+        let rows = read_day(path, range)?;
+        coverage.add(&day, rows.first().map(&key).zip(rows.last().map(&key)));
+        days_rows.push(rows);
+    }
+
+    Ok((merge_sorted(days_rows, key), coverage))
+}
+
+// transaction data of one day file in the time range sorted by timestamp
+pub(crate) fn read_transaction_data_day(
+    path: impl AsRef<Path>,
+    range: &TimeRange,
+) -> Result<Vec<TransactionData>, TransactionRangeError> {
+    let result = scan_day_file(path, range)?.collect()?;
+    transaction_data_from_dataframe(&result)
+}
+
+// transaction data of the transaction-data columns, the columns of the newer layouts are optional
+pub(crate) fn transaction_data_from_dataframe(
+    result: &DataFrame,
+) -> Result<Vec<TransactionData>, TransactionRangeError> {
+    let mut transactions = Vec::with_capacity(result.height());
+
+    let timestamp = result.column("timestamp")?.datetime()?;
+    let hash = result.column("hash")?.str()?;
+    let chain_id = result.column("chainId")?.str()?;
+    let from = result.column("from")?.str()?;
+    let to = result.column("to")?.str()?;
+    let value = result.column("value")?.str()?;
+    let nonce = result.column("nonce")?.str()?;
+    let gas = result.column("gas")?.str()?;
+    let gas_price = result.column("gasPrice")?.str()?;
+    let gas_tip_cap = result.column("gasTipCap")?.str()?;
+    let gas_fee_cap = result.column("gasFeeCap")?.str()?;
+    let data_size = result.column("dataSize")?.i64()?;
+    let data_4bytes = result.column("data4Bytes")?.str()?;
+    // columns of the newer upstream layouts, missing in older files
+    let sources = optional_column(result, "sources", |s| s.list().cloned())?;
+    let included_at_block_height =
+        optional_column(result, "includedAtBlockHeight", |s| s.i64().cloned())?;
+    let included_block_timestamp =
+        optional_column(result, "includedBlockTimestamp", |s| s.i64().cloned())?;
+    let inclusion_delay_ms = optional_column(result, "inclusionDelayMs", |s| s.i64().cloned())?;
+    let tx_type = optional_column(result, "txType", |s| s.i64().cloned())?;
+    let blob_gas_fee_cap = optional_column(result, "blobGasFeeCap", |s| s.str().cloned())?;
+    let blob_hashes = optional_column(result, "blobHashes", |s| s.list().cloned())?;
+
+    let get_str = |column: &polars::prelude::StringChunked, i: usize| {
+        column.get(i).unwrap_or_default().to_string()
+    };
+    let get_i64 = |column: &Option<polars::prelude::Int64Chunked>, i: usize| {
+        column.as_ref().and_then(|c| c.get(i))
+    };
+    let get_list = |column: &Option<polars::prelude::ListChunked>, i: usize| {
+        let list = column.as_ref()?.get_as_series(i)?;
+        Some(
+            list.str()
+                .ok()?
+                .into_iter()
+                .flatten()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    for i in 0..result.height() {
+        let timestamp_ms = timestamp.get(i).ok_or_else(|| {
+            TransactionRangeError::PolarsError(PolarsError::NoData("timestamp".into()))
+        })?;
+
+        transactions.push(TransactionData {
+            timestamp_ms,
+            hash: get_str(hash, i),
+            chain_id: get_str(chain_id, i),
+            from: get_str(from, i),
+            to: get_str(to, i),
+            value: get_str(value, i),
+            nonce: get_str(nonce, i),
+            gas: get_str(gas, i),
+            gas_price: get_str(gas_price, i),
+            gas_tip_cap: get_str(gas_tip_cap, i),
+            gas_fee_cap: get_str(gas_fee_cap, i),
+            data_size: data_size.get(i).unwrap_or_default(),
+            data_4bytes: get_str(data_4bytes, i),
+            sources: get_list(&sources, i),
+            included_at_block_height: get_i64(&included_at_block_height, i),
+            included_block_timestamp_ms: get_i64(&included_block_timestamp, i),
+            inclusion_delay_ms: get_i64(&inclusion_delay_ms, i),
+            tx_type: get_i64(&tx_type, i),
+            blob_gas_fee_cap: blob_gas_fee_cap
+                .as_ref()
+                .and_then(|c| c.get(i))
+                .map(|s| s.to_string()),
+            blob_hashes: get_list(&blob_hashes, i),
+        })
+    }
+    Ok(transactions)
+}
+
+// column of the dataframe if present
+fn optional_column<T>(
+    df: &DataFrame,
+    name: &str,
+    cast: impl Fn(&Series) -> Result<T, PolarsError>,
+) -> Result<Option<T>, PolarsError> {
+    df.column(name).ok().map(cast).transpose()
+}
+
+// Scans rows of one day file in the time range sorted by timestamp. Files written by this crate
+// are sorted so only row groups that can contain the range are read, other files are sorted.
+pub(crate) fn scan_day_file(
+    path: impl AsRef<Path>,
+    range: &TimeRange,
+) -> Result<LazyFrame, TransactionRangeError> {
+    let path = path.as_ref();
+    let rows = parquet::timestamp_row_range(path, range)?;
+    let df = match rows {
+        Some((start, end)) => {
+            tracing::trace!("Reading rows {}..{} of {}", start, end, path.display());
+            let args = ScanArgsParquet {
+                n_rows: Some(end),
+                ..Default::default()
+            };
+            LazyFrame::scan_parquet(path, args)?.slice(
+                start as i64,
+                IdxSize::try_from(end - start).unwrap_or(IdxSize::MAX),
+            )
+        }
+        None => LazyFrame::scan_parquet(path, Default::default())?,
+    };
+    let df = df.filter(
+        col("timestamp")
+            .gt_eq(range.start_ms())
+            .and(col("timestamp").lt(range.end_ms())),
+    );
+    Ok(match rows {
+        Some(_) => df,
+        None => df.sort(
+            ["timestamp"],
+            SortMultipleOptions::default().with_maintain_order(true),
+        ),
+    })
+}
+
+// k-way merge of per day results sorted by the key, equal keys keep the day order
+pub(crate) fn merge_sorted<T>(days: Vec<Vec<T>>, key: impl Fn(&T) -> i64) -> Vec<T> {
+    let mut result = Vec::with_capacity(days.iter().map(|d| d.len()).sum());
+    let mut days = days
+        .into_iter()
+        .map(|d| d.into_iter().peekable())
+        .collect::<Vec<_>>();
+
+    let mut heap = BinaryHeap::new();
+    for (i, day) in days.iter_mut().enumerate() {
+        if let Some(item) = day.peek() {
+            heap.push(Reverse((key(item), i)));
+        }
+    }
+    while let Some(Reverse((_, i))) = heap.pop() {
+        if let Some(item) = days[i].next() {
+            result.push(item);
+        }
+        if let Some(item) = days[i].peek() {
+            heap.push(Reverse((key(item), i)));
+        }
+    }
+    result
+}
+
+// day files of the days, all of them must exist
+pub(crate) fn day_paths(
+    data_dir: &Path,
+    kind: DataKind,
+    days: &[String],
+) -> Result<Vec<PathBuf>, TransactionRangeError> {
+    days.iter()
+        .map(|day| {
+            existing_day_path(data_dir, kind, day)
+                .ok_or_else(|| TransactionRangeError::DayFileNotFound(day.to_string()))
+        })
+        .collect()
+}
+
+// day files of the days, missing days have no path if they are skipped
+fn find_day_paths(
+    data_dir: &Path,
+    kind: DataKind,
+    days: Vec<String>,
+    skip_missing: bool,
+) -> Result<Vec<(String, Option<PathBuf>)>, TransactionRangeError> {
+    days.into_iter()
+        .map(|day| match existing_day_path(data_dir, kind, &day) {
+            None if !skip_missing => Err(TransactionRangeError::DayFileNotFound(day)),
+            path => Ok((day, path)),
+        })
+        .collect()
+}
+
+// get all days in range
+pub(crate) fn days_in_range(range: &TimeRange) -> Result<Vec<String>, TransactionRangeError> {
+    if range.is_empty() {
+        return Ok(Vec::new());
+    }
+    let from_time = naive_datetime_from_millis(range.start_ms())
+        .ok_or(TransactionRangeError::InvalidTimestamp)?;
+    // last timestamp in the range
+    let to_time = naive_datetime_from_millis(range.end_ms() - 1)
+        .ok_or(TransactionRangeError::InvalidTimestamp)?;
+
+    tracing::trace!("Getting days from {} to {}", from_time, to_time);
+
+    let mut days = Vec::new();
+    let mut current_day = from_time.date();
+    while current_day <= to_time.date() {
+        days.push(current_day.format("%Y-%m-%d").to_string());
+        current_day = current_day
+            .succ_opt()
+            .ok_or(TransactionRangeError::InvalidTimestamp)?;
+    }
+    Ok(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use tracing::debug;
+
+    #[ignore]
+    #[test]
+    fn test_get_raw_transactions() {
+        let env = tracing_subscriber::EnvFilter::builder()
+            .parse("mempool_dumpster=trace")
+            .unwrap();
+        tracing_subscriber::fmt().with_env_filter(env).init();
+
+        // 2023-09-09 01:00:00
+        let start_time = NaiveDate::from_ymd_opt(2023, 9, 9)
+            .unwrap()
+            .and_hms_opt(1, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis();
+        // 2023-09-09 01:05:00
+        let end_time = NaiveDate::from_ymd_opt(2023, 9, 9)
+            .unwrap()
+            .and_hms_opt(1, 5, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis();
+
+        let res = get_raw_transactions("data", (start_time, end_time)).unwrap();
+        debug!("Got {} transactions", res.len());
+        debug!("First transaction: {:?}", res.first());
+        debug!("Last transaction: {:?}", res.last());
+    }
+
+    #[test]
+    fn test_merge_sorted() {
+        let days = vec![
+            vec![(1, "a"), (5, "a"), (9, "a")],
+            vec![],
+            vec![(1, "c"), (6, "c")],
+        ];
+        assert_eq!(
+            merge_sorted(days, |r| r.0),
+            vec![(1, "a"), (1, "c"), (5, "a"), (6, "c"), (9, "a")]
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Disk usage of the data directory and pruning of old day files.
+
+use crate::data_dir::legacy_day_path;
+use crate::lock::{lock_file, LockMode};
+use crate::{DataKind, Manifest};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Day file of the data directory.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct DayFile {
+    pub kind: DataKind,
+    pub day: String,
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// Day files of the kind, sorted by day. Other files in the kind directories are ignored.
+pub fn day_files(data_dir: impl AsRef<Path>, kind: DataKind) -> eyre::Result<Vec<DayFile>> {
+    let data_dir = data_dir.as_ref();
+    let dir = data_dir.join(kind.name());
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(day) = name.get(..10) else {
+            continue;
+        };
+        if chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d").is_err() {
+            continue;
+        }
+        // files of directories that weren't migrated yet are included
+        let path = entry.path();
+        if path != kind.path(data_dir, day)
+            && Some(&path) != legacy_day_path(data_dir, kind, day).as_ref()
+        {
+            continue;
+        }
+        files.push(DayFile {
+            kind,
+            day: day.to_string(),
+            path,
+            bytes: entry.metadata()?.len(),
+        });
+    }
+    files.sort_by(|a, b| a.day.cmp(&b.day));
+    Ok(files)
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct Usage {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Bytes of the day files per kind and month, keyed by kind name and `YYYY-MM`.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub total: Usage,
+    pub kinds: BTreeMap<String, Usage>,
+    pub months: BTreeMap<String, BTreeMap<String, Usage>>,
+}
+
+pub fn disk_usage(data_dir: impl AsRef<Path>) -> eyre::Result<DiskUsage> {
+    let mut usage = DiskUsage::default();
+    for kind in DataKind::ALL {
+        for file in day_files(&data_dir, kind)? {
+            usage.total.add(file.bytes);
+            usage
+                .kinds
+                .entry(kind.name().to_string())
+                .or_default()
+                .add(file.bytes);
+            usage
+                .months
+                .entry(crate::get_month(&file.day))
+                .or_default()
+                .entry(kind.name().to_string())
+                .or_default()
+                .add(file.bytes);
+        }
+    }
+    Ok(usage)
+}
+
+/// Which day files are removed by [`prune`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// keep the files of the last days (today included), older files are removed
+    pub keep_days: Option<u32>,
+    /// kinds that are pruned, all kinds if empty
+    pub kinds: Vec<DataKind>,
+    /// remove the oldest files of the pruned kinds until all day files fit in this size
+    pub max_size: Option<u64>,
+    /// only report the files that would be removed
+    pub dry_run: bool,
+}
+
+impl RetentionPolicy {
+    pub fn with_keep_days(mut self, keep_days: Option<u32>) -> Self {
+        self.keep_days = keep_days;
+        self
+    }
+
+    pub fn with_kinds(mut self, kinds: Vec<DataKind>) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    fn kinds(&self) -> Vec<DataKind> {
+        if self.kinds.is_empty() {
+            DataKind::ALL.to_vec()
+        } else {
+            self.kinds.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    /// removed files, or the files that would be removed in a dry run
+    pub removed: Vec<DayFile>,
+    pub removed_bytes: u64,
+    /// bytes of the day files left
+    pub remaining_bytes: u64,
+}
+
+/// Removes day files according to the policy, oldest first, and drops them from the manifest.
+pub fn prune(data_dir: impl AsRef<Path>, policy: &RetentionPolicy) -> eyre::Result<PruneReport> {
+    let data_dir = data_dir.as_ref();
+    let kinds = policy.kinds();
+
+    let mut total_bytes = 0;
+    let mut candidates = Vec::new();
+    for kind in DataKind::ALL {
+        let files = day_files(data_dir, kind)?;
+        total_bytes += files.iter().map(|f| f.bytes).sum::<u64>();
+        if kinds.contains(&kind) {
+            candidates.extend(files);
+        }
+    }
+    // oldest day first, sourcelog before transaction-data before transactions
+    candidates.sort_by_key(|f| {
+        (
+            f.day.clone(),
+            DataKind::ALL.iter().position(|k| *k == f.kind),
+        )
+    });
+
+    let cutoff = policy.keep_days.map(|keep_days| {
+        let today = chrono::Utc::now().date_naive();
+        (today - chrono::Days::new(keep_days.saturating_sub(1) as u64))
+            .format("%Y-%m-%d")
+            .to_string()
+    });
+    let mut remaining_bytes = total_bytes;
+    let mut removed = Vec::new();
+    for file in candidates {
+        let expired = cutoff.as_ref().is_some_and(|cutoff| file.day < *cutoff);
+        let over_size = policy.max_size.is_some_and(|max| remaining_bytes > max);
+        if !expired && !over_size {
+            continue;
+        }
+        remaining_bytes -= file.bytes;
+        removed.push(file);
+    }
+
+    if !policy.dry_run && !removed.is_empty() {
+        let mut kept = Vec::new();
+        for file in &removed {
+            // files that are written right now are kept
+            let Some(_lock) = lock_file(data_dir, &file.path, LockMode::Skip)? else {
+                info!("File {} is locked, keeping it", file.path.display());
+                remaining_bytes += file.bytes;
+                kept.push(file.path.clone());
+                continue;
+            };
+            info!("Removing {}", file.path.display());
+            fs::remove_file(&file.path)?;
+        }
+        removed.retain(|file| !kept.contains(&file.path));
+        Manifest::update(data_dir, |manifest| {
+            for file in &removed {
+                manifest.remove(data_dir, &file.path);
+            }
+        })?;
+    }
+
+    Ok(PruneReport {
+        dry_run: policy.dry_run,
+        removed_bytes: total_bytes - remaining_bytes,
+        removed,
+        remaining_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConversionReport;
+
+    #[test]
+    fn test_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path();
+        let today = chrono::Utc::now().date_naive();
+        let days = [200, 100, 1, 0].map(|ago| {
+            (today - chrono::Days::new(ago))
+                .format("%Y-%m-%d")
+                .to_string()
+        });
+
+        let mut manifest = Manifest::default();
+        for kind in [DataKind::Sourcelog, DataKind::Transactions] {
+            fs::create_dir_all(data_dir.join(kind.name())).unwrap();
+            for day in &days {
+                let path = kind.path(data_dir, day);
+                fs::write(&path, vec![0u8; 100]).unwrap();
+                manifest.files.insert(
+                    path.strip_prefix(data_dir)
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string(),
+                    ConversionReport::default(),
+                );
+            }
+        }
+        manifest.save(data_dir).unwrap();
+        // not a day file
+        fs::write(data_dir.join("transactions/notes.txt"), "keep").unwrap();
+
+        let usage = disk_usage(data_dir).unwrap();
+        assert_eq!(
+            usage.total,
+            Usage {
+                files: 8,
+                bytes: 800
+            }
+        );
+        assert_eq!(
+            usage.kinds["transactions"],
+            Usage {
+                files: 4,
+                bytes: 400
+            }
+        );
+        let month = crate::get_month(&days[3]);
+        let days_in_month = days.iter().filter(|d| crate::get_month(d) == month).count();
+        assert_eq!(
+            usage.months[&month]["sourcelog"].bytes,
+            days_in_month as u64 * 100
+        );
+
+        let policy = RetentionPolicy::default()
+            .with_keep_days(Some(30))
+            .with_kinds(vec![DataKind::Transactions])
+            .with_dry_run(true);
+        let report = prune(data_dir, &policy).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert_eq!(report.removed_bytes, 200);
+        assert!(report.removed.iter().all(|f| f.path.exists()));
+
+        let report = prune(data_dir, &policy.with_dry_run(false)).unwrap();
+        assert_eq!(report.remaining_bytes, 600);
+        assert!(!DataKind::Transactions.path(data_dir, &days[0]).exists());
+        assert!(DataKind::Sourcelog.path(data_dir, &days[0]).exists());
+        assert!(data_dir.join("transactions/notes.txt").exists());
+        let manifest = Manifest::load(data_dir).unwrap();
+        assert!(manifest
+            .get(data_dir, DataKind::Transactions.path(data_dir, &days[0]))
+            .is_none());
+        assert_eq!(manifest.files.len(), 6);
+
+        // oldest files of all kinds until 300 bytes are left
+        let report = prune(
+            data_dir,
+            &RetentionPolicy::default().with_max_size(Some(300)),
+        )
+        .unwrap();
+        assert_eq!(report.remaining_bytes, 300);
+        assert_eq!(
+            report.removed.iter().map(|f| f.kind).collect::<Vec<_>>(),
+            vec![
+                DataKind::Sourcelog,
+                DataKind::Sourcelog,
+                DataKind::Sourcelog
+            ]
+        );
+        assert!(DataKind::Sourcelog.path(data_dir, &days[3]).exists());
+    }
+
+    #[test]
+    fn test_day_files_of_legacy_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("transactions")).unwrap();
+        let legacy = dir.path().join("transactions/2023-09-01.parquet");
+        fs::write(&legacy, "parquet").unwrap();
+        let current = DataKind::Transactions.path(dir.path(), "2023-09-02");
+        fs::write(&current, "parquet").unwrap();
+
+        let files = day_files(dir.path(), DataKind::Transactions).unwrap();
+        assert_eq!(
+            files.iter().map(|f| &f.path).collect::<Vec<_>>(),
+            vec![&legacy, &current]
+        );
+
+        let report = prune(
+            dir.path(),
+            &RetentionPolicy::default().with_keep_days(Some(1)),
+        )
+        .unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert!(!legacy.exists());
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn test_prune_leaves_no_lock_files() {
+        let fetcher = crate::fetch::MemoryFetcher::new();
+        fetcher.insert(
+            "2023-09/2023-09-01_sourcelog.csv.zip",
+            crate::test_utils::sourcelog_zip("2023-09-01", &[1_693_526_400_001]),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let config = crate::Config::new(dir.path())
+            .with_progress(false)
+            .with_fetcher(fetcher);
+        config.download_sourcelog_file("2023-09-01").unwrap();
+
+        let report = prune(
+            dir.path(),
+            &RetentionPolicy::default().with_keep_days(Some(1)),
+        )
+        .unwrap();
+        assert_eq!(report.removed.len(), 1);
+
+        let mut lock_files = Vec::new();
+        let mut dirs = vec![dir.path().to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|e| e == "lock") {
+                    lock_files.push(path);
+                }
+            }
+        }
+        let locks_dir = dir.path().join(crate::lock::LOCKS_DIR);
+        assert!(
+            lock_files.iter().all(|path| path.starts_with(&locks_dir)),
+            "{:?}",
+            lock_files
+        );
+        assert!(day_files(dir.path(), DataKind::Sourcelog)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::DataKind;
+#[cfg(feature = "download")]
+use polars::prelude::{DataType, NamedFrom, Series};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Column added upstream after the first version of the csv files. The converters deserialize it
+/// when present in the csv header and write nulls otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OptionalColumn {
+    pub csv_name: &'static str,
+    pub parquet_name: &'static str,
+}
+
+impl OptionalColumn {
+    const fn new(csv_name: &'static str, parquet_name: &'static str) -> Self {
+        Self {
+            csv_name,
+            parquet_name,
+        }
+    }
+}
+
+/// List of strings column from comma separated values.
+#[cfg(feature = "download")]
+pub(crate) fn list_series<'a>(name: &str, values: impl Iterator<Item = Option<&'a str>>) -> Series {
+    let lists = values
+        .map(|v| {
+            v.map(|v| {
+                Series::new(
+                    "",
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .collect::<Vec<_>>(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    // all null lists don't have the inner type
+    Series::new(name, lists)
+        .cast(&DataType::List(Box::new(DataType::String)))
+        .expect("list of strings")
+}
+
+/// Known layout of the upstream csv files. A header matches the schema if it has all required
+/// columns and every other column is one of the optional columns, after renaming the aliases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CsvSchema {
+    pub kind: DataKind,
+    pub version: u32,
+    pub required: &'static [&'static str],
+    pub optional: &'static [OptionalColumn],
+    /// columns renamed upstream, `(name in the csv header, required or optional column)`
+    pub aliases: &'static [(&'static str, &'static str)],
+}
+
+const SOURCELOG_COLUMNS: &[&str] = &["timestamp_ms", "hash", "source"];
+
+const TRANSACTION_DATA_COLUMNS: &[&str] = &[
+    "timestamp_ms",
+    "hash",
+    "chain_id",
+    "from",
+    "to",
+    "value",
+    "nonce",
+    "gas",
+    "gas_price",
+    "gas_tip_cap",
+    "gas_fee_cap",
+    "data_size",
+    "data_4bytes",
+];
+
+const TRANSACTION_DATA_V2_COLUMNS: &[OptionalColumn] = &[
+    OptionalColumn::new("sources", "sources"),
+    OptionalColumn::new("included_at_block_height", "includedAtBlockHeight"),
+    OptionalColumn::new("included_block_timestamp", "includedBlockTimestamp"),
+    OptionalColumn::new("inclusion_delay_ms", "inclusionDelayMs"),
+];
+
+const TRANSACTION_DATA_V3_COLUMNS: &[OptionalColumn] = &[
+    OptionalColumn::new("sources", "sources"),
+    OptionalColumn::new("included_at_block_height", "includedAtBlockHeight"),
+    OptionalColumn::new("included_block_timestamp", "includedBlockTimestamp"),
+    OptionalColumn::new("inclusion_delay_ms", "inclusionDelayMs"),
+    OptionalColumn::new("tx_type", "txType"),
+    OptionalColumn::new("blob_gas_fee_cap", "blobGasFeeCap"),
+    OptionalColumn::new("blob_hashes", "blobHashes"),
+];
+
+/// Known csv layouts, oldest first.
+pub const SCHEMAS: &[CsvSchema] = &[
+    CsvSchema {
+        kind: DataKind::Sourcelog,
+        version: 1,
+        required: SOURCELOG_COLUMNS,
+        optional: &[],
+        aliases: &[],
+    },
+    CsvSchema {
+        kind: DataKind::TransactionData,
+        version: 1,
+        required: TRANSACTION_DATA_COLUMNS,
+        optional: &[],
+        aliases: &[],
+    },
+    CsvSchema {
+        kind: DataKind::TransactionData,
+        version: 2,
+        required: TRANSACTION_DATA_COLUMNS,
+        optional: TRANSACTION_DATA_V2_COLUMNS,
+        aliases: &[],
+    },
+    CsvSchema {
+        kind: DataKind::TransactionData,
+        version: 3,
+        required: TRANSACTION_DATA_COLUMNS,
+        optional: TRANSACTION_DATA_V3_COLUMNS,
+        aliases: &[],
+    },
+];
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    #[error("no csv schema for {0}")]
+    UnsupportedKind(DataKind),
+    #[error(
+        "unknown {kind} csv layout, missing columns: {missing:?}, unknown columns: {unknown:?}"
+    )]
+    UnknownLayout {
+        kind: DataKind,
+        missing: Vec<String>,
+        unknown: Vec<String>,
+    },
+}
+
+impl CsvSchema {
+    fn matches(&self, header: &HashSet<&str>) -> bool {
+        let header = self.rename(header);
+        self.required.iter().all(|c| header.contains(c))
+            && header.iter().all(|c| {
+                self.required.contains(c) || self.optional.iter().any(|o| o.csv_name == *c)
+            })
+    }
+
+    fn rename<'a>(&self, header: &HashSet<&'a str>) -> HashSet<&'a str> {
+        header.iter().map(|c| self.column_name(c)).collect()
+    }
+
+    /// Name of the csv column in the schema, aliases are renamed to the current name.
+    pub fn column_name<'a>(&self, csv_name: &'a str) -> &'a str {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| *alias == csv_name)
+            .map_or(csv_name, |(_, name)| name)
+    }
+
+    /// Optional column with the given csv name.
+    pub fn optional_column(&self, csv_name: &str) -> Option<&'static OptionalColumn> {
+        self.optional.iter().find(|c| c.csv_name == csv_name)
+    }
+}
+
+/// Finds the oldest known schema of the kind matching the csv header.
+pub fn detect_schema<'a>(
+    kind: DataKind,
+    header: impl IntoIterator<Item = &'a str>,
+) -> Result<&'static CsvSchema, SchemaError> {
+    detect_schema_in(SCHEMAS, kind, header)
+}
+
+/// Finds the oldest schema of the kind in `schemas` matching the csv header.
+pub(crate) fn detect_schema_in<'a>(
+    schemas: &'static [CsvSchema],
+    kind: DataKind,
+    header: impl IntoIterator<Item = &'a str>,
+) -> Result<&'static CsvSchema, SchemaError> {
+    let header = header.into_iter().collect::<HashSet<_>>();
+    let mut schemas = schemas.iter().filter(|s| s.kind == kind).peekable();
+    if schemas.peek().is_none() {
+        return Err(SchemaError::UnsupportedKind(kind));
+    }
+    let mut latest = None;
+    for schema in schemas {
+        if schema.matches(&header) {
+            return Ok(schema);
+        }
+        latest = Some(schema);
+    }
+
+    // report the difference to the latest version
+    let latest = latest.expect("at least one schema");
+    let header = latest.rename(&header);
+    let mut missing = latest
+        .required
+        .iter()
+        .filter(|c| !header.contains(*c))
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    missing.sort();
+    let mut unknown = header
+        .iter()
+        .filter(|c| !latest.required.contains(c) && latest.optional_column(c).is_none())
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    unknown.sort();
+    Err(SchemaError::UnknownLayout {
+        kind,
+        missing,
+        unknown,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_schema() {
+        let v1 = TRANSACTION_DATA_COLUMNS.to_vec();
+        assert_eq!(
+            detect_schema(DataKind::TransactionData, v1.clone())
+                .unwrap()
+                .version,
+            1
+        );
+
+        let mut v2 = v1.clone();
+        v2.extend(["sources", "inclusion_delay_ms"]);
+        assert_eq!(
+            detect_schema(DataKind::TransactionData, v2.clone())
+                .unwrap()
+                .version,
+            2
+        );
+
+        let mut v3 = v2.clone();
+        v3.push("blob_hashes");
+        assert_eq!(
+            detect_schema(DataKind::TransactionData, v3)
+                .unwrap()
+                .version,
+            3
+        );
+
+        let mut unknown = v1[1..].to_vec();
+        unknown.push("timestamp");
+        assert_eq!(
+            detect_schema(DataKind::TransactionData, unknown),
+            Err(SchemaError::UnknownLayout {
+                kind: DataKind::TransactionData,
+                missing: vec!["timestamp_ms".to_string()],
+                unknown: vec!["timestamp".to_string()],
+            })
+        );
+
+        assert_eq!(
+            detect_schema(DataKind::Transactions, v1),
+            Err(SchemaError::UnsupportedKind(DataKind::Transactions))
+        );
+    }
+
+    #[test]
+    fn test_renamed_columns() {
+        // version 2 renamed `source` to `origin`
+        const SCHEMAS: &[CsvSchema] = &[
+            CsvSchema {
+                kind: DataKind::Sourcelog,
+                version: 1,
+                required: SOURCELOG_COLUMNS,
+                optional: &[],
+                aliases: &[],
+            },
+            CsvSchema {
+                kind: DataKind::Sourcelog,
+                version: 2,
+                required: SOURCELOG_COLUMNS,
+                optional: &[],
+                aliases: &[("origin", "source")],
+            },
+        ];
+
+        let renamed = ["timestamp_ms", "hash", "origin"];
+        let schema = detect_schema_in(SCHEMAS, DataKind::Sourcelog, renamed).unwrap();
+        assert_eq!(schema.version, 2);
+        assert_eq!(
+            renamed.map(|c| schema.column_name(c)),
+            ["timestamp_ms", "hash", "source"]
+        );
+
+        let current = ["timestamp_ms", "hash", "source"];
+        let schema = detect_schema_in(SCHEMAS, DataKind::Sourcelog, current).unwrap();
+        assert_eq!(schema.version, 1);
+
+        // an alias is not an unknown column of the latest version
+        assert_eq!(
+            detect_schema_in(SCHEMAS, DataKind::Sourcelog, ["timestamp_ms", "origin"]),
+            Err(SchemaError::UnknownLayout {
+                kind: DataKind::Sourcelog,
+                missing: vec!["hash".to_string()],
+                unknown: vec![],
+            })
+        );
+    }
+}
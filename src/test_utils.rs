@@ -0,0 +1,206 @@
+//! Fixtures shared by the tests.
+
+#[cfg(any(feature = "download", feature = "analysis"))]
+use crate::TransactionData;
+#[cfg(feature = "download")]
+use std::path::Path;
+
+/// Transaction from `0xaa` to `0xbb` with nonce 0, zero value and all fees set to 1.
+#[cfg(any(feature = "download", feature = "analysis"))]
+pub(crate) fn tx(timestamp_ms: i64, hash: &str) -> TransactionData {
+    TransactionData {
+        timestamp_ms,
+        hash: hash.to_string(),
+        chain_id: "1".to_string(),
+        from: "0xaa".to_string(),
+        to: "0xbb".to_string(),
+        value: "0".to_string(),
+        nonce: "0".to_string(),
+        gas: "21000".to_string(),
+        gas_price: "1".to_string(),
+        gas_tip_cap: "1".to_string(),
+        gas_fee_cap: "1".to_string(),
+        data_size: 0,
+        data_4bytes: "".to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(any(feature = "download", feature = "analysis"))]
+impl TransactionData {
+    pub(crate) fn with_from(mut self, from: &str) -> Self {
+        self.from = from.to_string();
+        self
+    }
+
+    pub(crate) fn with_to(mut self, to: &str) -> Self {
+        self.to = to.to_string();
+        self
+    }
+
+    #[cfg(feature = "analysis")]
+    pub(crate) fn with_value(mut self, value: &str) -> Self {
+        self.value = value.to_string();
+        self
+    }
+
+    #[cfg(feature = "analysis")]
+    pub(crate) fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = nonce.to_string();
+        self
+    }
+
+    pub(crate) fn with_gas_price(mut self, gas_price: &str) -> Self {
+        self.gas_price = gas_price.to_string();
+        self
+    }
+
+    #[cfg(feature = "analysis")]
+    pub(crate) fn with_gas_tip_cap(mut self, gas_tip_cap: &str) -> Self {
+        self.gas_tip_cap = gas_tip_cap.to_string();
+        self
+    }
+
+    #[cfg(feature = "analysis")]
+    pub(crate) fn with_gas_fee_cap(mut self, gas_fee_cap: &str) -> Self {
+        self.gas_fee_cap = gas_fee_cap.to_string();
+        self
+    }
+
+    pub(crate) fn with_data_4bytes(mut self, data_4bytes: &str) -> Self {
+        self.data_4bytes = data_4bytes.to_string();
+        self
+    }
+
+    #[cfg(feature = "download")]
+    pub(crate) fn with_sources(mut self, sources: &[&str]) -> Self {
+        self.sources = Some(sources.iter().map(|s| s.to_string()).collect());
+        self
+    }
+
+    #[cfg(feature = "analysis")]
+    pub(crate) fn with_tx_type(mut self, tx_type: i64) -> Self {
+        self.tx_type = Some(tx_type);
+        self
+    }
+
+    #[cfg(feature = "analysis")]
+    pub(crate) fn with_included(mut self, block_height: i64, block_timestamp_ms: i64) -> Self {
+        self.included_at_block_height = Some(block_height);
+        self.included_block_timestamp_ms = Some(block_timestamp_ms);
+        self.inclusion_delay_ms = Some(block_timestamp_ms - self.timestamp_ms);
+        self
+    }
+}
+
+/// Writes the transactions to the transaction-data file of the day.
+#[cfg(feature = "download")]
+pub(crate) fn write_transaction_data(data_dir: &Path, day: &str, txs: &[TransactionData]) {
+    let path = crate::path_transaction_data(data_dir, day);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    crate::write_dataframe_to_parquet(
+        transaction_data_frame(txs),
+        path,
+        &crate::ParquetOptions::default(),
+    )
+    .unwrap();
+}
+
+/// Writes `(timestamp_ms, hash, source)` rows to the sourcelog file of the day.
+#[cfg(feature = "download")]
+pub(crate) fn write_sourcelog(data_dir: &Path, day: &str, rows: &[(i64, &str, &str)]) {
+    use polars::prelude::NamedFrom;
+
+    let df = polars::frame::DataFrame::new(vec![
+        polars::series::Series::new(
+            "timestamp",
+            rows.iter()
+                .map(|r| crate::naive_datetime_from_millis(r.0))
+                .collect::<Vec<_>>(),
+        ),
+        polars::series::Series::new("hash", rows.iter().map(|r| r.1).collect::<Vec<_>>()),
+        polars::series::Series::new("source", rows.iter().map(|r| r.2).collect::<Vec<_>>()),
+    ])
+    .unwrap();
+    let path = crate::path_source_log(data_dir, day);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    crate::write_dataframe_to_parquet(df, path, &crate::ParquetOptions::default()).unwrap();
+}
+
+/// Transactions as written to the transaction-data files.
+#[cfg(feature = "download")]
+pub(crate) fn transaction_data_frame(txs: &[TransactionData]) -> polars::frame::DataFrame {
+    let join = |list: &Option<Vec<String>>| list.as_ref().map(|l| l.join(","));
+    let records = txs
+        .iter()
+        .map(|tx| crate::download::TransactionDataCSVRecord {
+            timestamp_ms: tx.timestamp_ms,
+            hash: tx.hash.clone(),
+            chain_id: tx.chain_id.clone(),
+            from: tx.from.clone(),
+            to: tx.to.clone(),
+            value: tx.value.clone(),
+            nonce: tx.nonce.clone(),
+            gas: tx.gas.clone(),
+            gas_price: tx.gas_price.clone(),
+            gas_tip_cap: tx.gas_tip_cap.clone(),
+            gas_fee_cap: tx.gas_fee_cap.clone(),
+            data_size: tx.data_size,
+            data_4bytes: tx.data_4bytes.clone(),
+            sources: join(&tx.sources),
+            included_at_block_height: tx.included_at_block_height,
+            included_block_timestamp: tx.included_block_timestamp_ms,
+            inclusion_delay_ms: tx.inclusion_delay_ms,
+            tx_type: tx.tx_type,
+            blob_gas_fee_cap: tx.blob_gas_fee_cap.clone(),
+            blob_hashes: join(&tx.blob_hashes),
+        })
+        .collect::<Vec<_>>();
+    crate::download::transaction_data_dataframe(&records).unwrap()
+}
+
+/// Serves `body` to one HTTP request on localhost. Returns the server url and a handle returning
+/// the requested path.
+#[cfg(feature = "async")]
+pub(crate) fn serve_once(body: &str) -> (String, std::thread::JoinHandle<String>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let body = body.to_string();
+    let handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request_line = String::new();
+        BufReader::new(&stream)
+            .read_line(&mut request_line)
+            .unwrap();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string()
+    });
+    (url, handle)
+}
+
+/// Zipped sourcelog csv of the day with one `local` row per timestamp.
+#[cfg(feature = "download")]
+pub(crate) fn sourcelog_zip(day: &str, timestamps: &[i64]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file(format!("{}_sourcelog.csv", day), Default::default())
+        .unwrap();
+    writeln!(zip, "timestamp_ms,hash,source").unwrap();
+    for timestamp in timestamps {
+        writeln!(zip, "{},0x{},local", timestamp, timestamp).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
@@ -0,0 +1,263 @@
+//! Time ranges of the readers. Bounds are millisecond timestamps, the start is included and the end
+//! excluded unless set otherwise, so consecutive ranges don't overlap or leave gaps.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::ops::{Range, RangeInclusive};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TimeRange {
+    pub from_ms: i64,
+    pub to_ms: i64,
+    pub from_inclusive: bool,
+    pub to_inclusive: bool,
+}
+
+impl TimeRange {
+    /// `[from_ms, to_ms)`
+    pub fn new(from_ms: i64, to_ms: i64) -> Self {
+        Self {
+            from_ms,
+            to_ms,
+            from_inclusive: true,
+            to_inclusive: false,
+        }
+    }
+
+    /// `(from_ms, to_ms)`, both bounds excluded like the `(i64, i64)` ranges of the readers before
+    /// [`TimeRange`] was added.
+    pub fn exclusive(from_ms: i64, to_ms: i64) -> Self {
+        Self::new(from_ms, to_ms).with_from_inclusive(false)
+    }
+
+    pub fn from_datetimes(from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        Self::new(from.timestamp_millis(), to.timestamp_millis())
+    }
+
+    /// Range between two times in one of the formats of [`parse_time`].
+    pub fn parse(from: &str, to: &str) -> eyre::Result<Self> {
+        Ok(Self::from_datetimes(parse_time(from)?, parse_time(to)?))
+    }
+
+    /// Slots `first_slot..=last_slot` of the beacon chain. Blocks are proposed at the start of
+    /// their slot, so the range holds the transactions seen from the first block until the block
+    /// after the last one.
+    pub fn slots(clock: &SlotClock, first_slot: i64, last_slot: i64) -> Self {
+        Self::new(
+            clock.slot_start_ms(first_slot),
+            clock.slot_start_ms(last_slot + 1),
+        )
+    }
+
+    pub fn with_from_inclusive(mut self, from_inclusive: bool) -> Self {
+        self.from_inclusive = from_inclusive;
+        self
+    }
+
+    pub fn with_to_inclusive(mut self, to_inclusive: bool) -> Self {
+        self.to_inclusive = to_inclusive;
+        self
+    }
+
+    /// First timestamp in the range.
+    pub fn start_ms(&self) -> i64 {
+        if self.from_inclusive {
+            self.from_ms
+        } else {
+            self.from_ms.saturating_add(1)
+        }
+    }
+
+    /// First timestamp after the range.
+    pub fn end_ms(&self) -> i64 {
+        if self.to_inclusive {
+            self.to_ms.saturating_add(1)
+        } else {
+            self.to_ms
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end_ms() <= self.start_ms()
+    }
+
+    pub fn contains(&self, timestamp_ms: i64) -> bool {
+        self.start_ms() <= timestamp_ms && timestamp_ms < self.end_ms()
+    }
+
+    /// Widens the range to whole slots.
+    pub fn align_to_slots(&self, clock: &SlotClock) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::slots(
+            clock,
+            clock.slot_at(self.start_ms()),
+            clock.slot_at(self.end_ms() - 1),
+        )
+    }
+}
+
+impl std::fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = |timestamp_ms: i64| match DateTime::from_timestamp_millis(timestamp_ms) {
+            Some(time) => time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            None => timestamp_ms.to_string(),
+        };
+        write!(
+            f,
+            "{}{}, {}{}",
+            if self.from_inclusive { "[" } else { "(" },
+            time(self.from_ms),
+            time(self.to_ms),
+            if self.to_inclusive { "]" } else { ")" }
+        )
+    }
+}
+
+/// `[from_ms, to_ms)` like [`TimeRange::new`], use [`TimeRange::exclusive`] to exclude `from_ms`.
+impl From<(i64, i64)> for TimeRange {
+    fn from((from_ms, to_ms): (i64, i64)) -> Self {
+        Self::new(from_ms, to_ms)
+    }
+}
+
+impl From<Range<i64>> for TimeRange {
+    fn from(range: Range<i64>) -> Self {
+        Self::new(range.start, range.end)
+    }
+}
+
+impl From<RangeInclusive<i64>> for TimeRange {
+    fn from(range: RangeInclusive<i64>) -> Self {
+        Self::new(*range.start(), *range.end()).with_to_inclusive(true)
+    }
+}
+
+impl From<(DateTime<Utc>, DateTime<Utc>)> for TimeRange {
+    fn from((from, to): (DateTime<Utc>, DateTime<Utc>)) -> Self {
+        Self::from_datetimes(from, to)
+    }
+}
+
+impl From<Range<DateTime<Utc>>> for TimeRange {
+    fn from(range: Range<DateTime<Utc>>) -> Self {
+        Self::from_datetimes(range.start, range.end)
+    }
+}
+
+/// Parses rfc3339, `YYYY-MM-DD HH:MM:SS` (UTC), `YYYY-MM-DD` (start of the day UTC) or unix
+/// milliseconds.
+pub fn parse_time(value: &str) -> eyre::Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(time.and_utc());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc());
+    }
+    if let Some(time) = value
+        .parse::<i64>()
+        .ok()
+        .and_then(DateTime::from_timestamp_millis)
+    {
+        return Ok(time);
+    }
+    Err(eyre::eyre!("invalid time: {}", value))
+}
+
+/// Slots of a beacon chain, blocks since the merge are proposed at the start of a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlotClock {
+    pub genesis_ms: i64,
+    pub slot_ms: i64,
+}
+
+impl SlotClock {
+    pub const MAINNET: Self = Self {
+        genesis_ms: 1_606_824_023_000,
+        slot_ms: 12_000,
+    };
+    pub const SEPOLIA: Self = Self {
+        genesis_ms: 1_655_733_600_000,
+        slot_ms: 12_000,
+    };
+    pub const HOLESKY: Self = Self {
+        genesis_ms: 1_695_902_400_000,
+        slot_ms: 12_000,
+    };
+
+    pub fn for_network(network: &str) -> Option<Self> {
+        match network {
+            "mainnet" => Some(Self::MAINNET),
+            "sepolia" => Some(Self::SEPOLIA),
+            "holesky" => Some(Self::HOLESKY),
+            _ => None,
+        }
+    }
+
+    /// Slot of the timestamp, negative before genesis.
+    pub fn slot_at(&self, timestamp_ms: i64) -> i64 {
+        (timestamp_ms - self.genesis_ms).div_euclid(self.slot_ms)
+    }
+
+    pub fn slot_start_ms(&self, slot: i64) -> i64 {
+        self.genesis_ms + slot * self.slot_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_range() {
+        let range = TimeRange::parse("2023-09-01", "2023-09-01T00:00:01Z").unwrap();
+        assert_eq!(range, TimeRange::new(1_693_526_400_000, 1_693_526_401_000));
+        assert!(range.contains(range.from_ms));
+        assert!(!range.contains(range.to_ms));
+        assert_eq!(range, TimeRange::from(range.from_ms..range.to_ms));
+        assert_eq!(
+            range.to_string(),
+            "[2023-09-01T00:00:00Z, 2023-09-01T00:00:01Z)"
+        );
+
+        let range = range.with_from_inclusive(false).with_to_inclusive(true);
+        assert!(!range.contains(range.from_ms));
+        assert!(range.contains(range.to_ms));
+        assert_eq!(
+            (range.start_ms(), range.end_ms()),
+            (range.from_ms + 1, range.to_ms + 1)
+        );
+        assert_eq!(
+            TimeRange::from(range.from_ms..=range.to_ms).end_ms(),
+            range.to_ms + 1
+        );
+        assert!(TimeRange::new(5, 5).is_empty());
+
+        // tuples include the start, the exclusive constructor doesn't
+        assert!(TimeRange::from((5, 10)).contains(5));
+        let exclusive = TimeRange::exclusive(5, 10);
+        assert!(!exclusive.contains(5) && exclusive.contains(6) && !exclusive.contains(10));
+
+        assert_eq!(
+            parse_time("1693526400000").unwrap(),
+            parse_time("2023-09-01 00:00:00").unwrap()
+        );
+        assert!(parse_time("yesterday").is_err());
+
+        // slot 7_225_200 starts at 2023-09-01 00:00:23
+        let clock = SlotClock::MAINNET;
+        assert_eq!(clock.slot_at(1_693_526_423_000), 7_225_200);
+        let aligned = TimeRange::parse("2023-09-01T00:00:30Z", "2023-09-01T00:00:40Z")
+            .unwrap()
+            .align_to_slots(&clock);
+        assert_eq!(aligned, TimeRange::slots(&clock, 7_225_200, 7_225_201));
+        assert_eq!(aligned.start_ms(), 1_693_526_423_000);
+        assert_eq!(aligned.end_ms(), 1_693_526_447_000);
+    }
+}